@@ -0,0 +1,95 @@
+// compares holding every entry's possible fills as word clones with holding
+// them as the dictionary's ids, the way GridSolver does, for both plain lookups
+// and the ranked range lookups a ranked solve makes
+// run with cargo bench --bench interning
+
+extern crate gridsolver;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use gridsolver::basic_types::{Pattern, Word, WordId};
+use gridsolver::dict::{DictLookup, Dictionary, RankedDictLookup, RankedDictionary, UnrankedDict};
+use gridsolver::grid::Grid;
+
+// an allocator that counts every allocation made through it
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+// the solver looks every entry up again after each undo, so do it a few times
+const LOOKUPS: usize = 10;
+
+// run the closure, returning what it made along with its allocations, bytes and time
+fn measure<F: FnOnce() -> R, R>(f: F) -> (R, usize, usize, f64) {
+    let (allocs, bytes) = (ALLOCATIONS.load(Ordering::Relaxed), BYTES.load(Ordering::Relaxed));
+    let start = Instant::now();
+    let result = f();
+    let elapsed = start.elapsed();
+    let allocs = ALLOCATIONS.load(Ordering::Relaxed) - allocs;
+    let bytes = BYTES.load(Ordering::Relaxed) - bytes;
+    let ms = elapsed.as_secs() as f64 * 1000.0 + elapsed.subsec_nanos() as f64 / 1_000_000.0;
+    (result, allocs, bytes, ms)
+}
+
+// look every pattern up LOOKUPS times both ways, reporting the allocations and time of each
+fn compare<W, I>(label: &str, patterns: &[Pattern], words: W, ids: I)
+    where W: Fn(&Pattern) -> Vec<Word>, I: Fn(&Pattern) -> Vec<WordId> {
+    let (clones, clone_allocs, clone_bytes, clone_ms) = measure(|| {
+        let mut fills: Vec<Vec<Word>> = Vec::new();
+        for _ in 0..LOOKUPS {
+            fills = patterns.iter().map(&words).collect();
+        }
+        fills
+    });
+
+    let (held_ids, id_allocs, id_bytes, id_ms) = measure(|| {
+        let mut fills: Vec<Vec<WordId>> = Vec::new();
+        for _ in 0..LOOKUPS {
+            fills = patterns.iter().map(&ids).collect();
+        }
+        fills
+    });
+
+    let held: usize = clones.iter().map(|fills| fills.len()).sum();
+    assert_eq!(held, held_ids.iter().map(|fills| fills.len()).sum::<usize>());
+    println!("{}: {} entries, {} possible fills, looked up {} times", label, patterns.len(), held, LOOKUPS);
+    println!("  words: {:>9} allocations {:>11} bytes {:>8.1}ms", clone_allocs, clone_bytes, clone_ms);
+    println!("  ids:   {:>9} allocations {:>11} bytes {:>8.1}ms", id_allocs, id_bytes, id_ms);
+}
+
+fn main() {
+    let grid = Grid::new(5, 5).unwrap();
+    let patterns: Vec<_> = grid.entry_indices().into_iter()
+        .filter_map(|index| grid.entry_pattern(index))
+        .collect();
+
+    let dict = Dictionary::from_file("./assets/ukacd_utf8.txt").expect("couldn't load the dictionary");
+    compare("lookup", &patterns,
+            |pattern| dict.lookup_iter(pattern).cloned().collect(),
+            |pattern| dict.lookup_ids(pattern).collect());
+
+    // every word gets the default score, so the whole range is looked up
+    let ranked = <RankedDictionary as UnrankedDict>::from_file("./assets/ukacd_utf8.txt")
+        .expect("couldn't load the dictionary");
+    compare("lookup_range", &patterns,
+            |pattern| ranked.lookup_range(pattern, None, None),
+            |pattern| ranked.lookup_range_ids(pattern, None, None));
+}
@@ -0,0 +1,84 @@
+// times the ways a dictionary can look up the words matching a pattern
+// run with cargo bench --bench lookups
+
+extern crate gridsolver;
+
+use std::time::{Duration, Instant};
+
+use gridsolver::basic_types::Pattern;
+use gridsolver::dict::{DictLookup, Dictionary, TrieDictionary, UnrankedDict};
+
+const WORD_LIST: &str = "./assets/ukacd_utf8.txt";
+
+// run the closure, returning what it made and how long it took
+fn time<F: FnOnce() -> R, R>(f: F) -> (R, Duration) {
+    let start = Instant::now();
+    let result = f();
+    (result, start.elapsed())
+}
+
+// loading the word list and looking patterns up in a trie vs the length index
+fn trie_vs_dictionary(dict: &Dictionary, dict_load: Duration) {
+    let (trie, trie_load) = time(|| TrieDictionary::from_file(WORD_LIST).expect("couldn't load the trie"));
+
+    let patterns = ["C.T", "....", "Q....", "...E.", "S.....", "..A..T..", "...............", "XZ.", ""];
+    let (mut dict_lookups, mut trie_lookups) = (Duration::default(), Duration::default());
+    for pattern in &patterns {
+        let pattern = Pattern::from(*pattern);
+        let (mut expected, elapsed) = time(|| dict.lookup(&pattern));
+        dict_lookups += elapsed;
+        let (mut found, elapsed) = time(|| trie.lookup(&pattern));
+        trie_lookups += elapsed;
+        expected.sort();
+        found.sort();
+        assert_eq!(found, expected);
+    }
+    println!("loading: dictionary {:?}, trie {:?}", dict_load, trie_load);
+    println!("lookups: dictionary {:?}, trie {:?}", dict_lookups, trie_lookups);
+}
+
+// a pattern with a couple of rare letters, looked up through the letter index
+// vs checking every word of that length
+fn letter_index_vs_scan(dict: &Dictionary) {
+    const RUNS: u32 = 100;
+    let pattern = Pattern::from(".X..Z..");
+    let blank = Pattern::from(".......");
+    let (indexed, indexed_time) = time(|| {
+        (0..RUNS).map(|_| dict.lookup_iter(&pattern).count()).sum::<usize>()
+    });
+    let (scanned, scan_time) = time(|| {
+        (0..RUNS).map(|_| dict.lookup_iter(&blank).filter(|word| pattern.matches(word)).count()).sum::<usize>()
+    });
+    assert_eq!(indexed, scanned);
+    println!("{} x{}: letter index {:?}, scan {:?}", pattern, RUNS, indexed_time, scan_time);
+}
+
+// matching every word of a length against a pattern with Pattern::matches
+// vs through the dictionary, which keeps every word packed
+fn packed_vs_unpacked(dict: &Dictionary) {
+    let patterns = ["C.T", "....", "Q....", "...E.", "S.....", "..A..T..", "[AEIOU]...S", "...............", ".[^E]..E.."];
+    let (mut unpacked, mut packed, mut scanned) = (Duration::default(), Duration::default(), 0);
+    for pattern in &patterns {
+        let pattern = Pattern::from(*pattern);
+        let blank = Pattern::from(".".repeat(pattern.size()).as_str());
+        let ids = dict.lookup_ids(&blank).collect::<Vec<_>>();
+        scanned += ids.len();
+        let (expected, elapsed) = time(|| {
+            ids.iter().filter(|&&id| pattern.matches(dict.word(id))).collect::<Vec<_>>()
+        });
+        unpacked += elapsed;
+        let (found, elapsed) = time(|| {
+            ids.iter().filter(|&&id| dict.id_matches(id, &pattern)).collect::<Vec<_>>()
+        });
+        packed += elapsed;
+        assert_eq!(found, expected);
+    }
+    println!("scanning {} words: pattern {:?}, packed {:?}", scanned, unpacked, packed);
+}
+
+fn main() {
+    let (dict, dict_load) = time(|| <Dictionary as UnrankedDict>::from_file(WORD_LIST).expect("couldn't load the dictionary"));
+    trie_vs_dictionary(&dict, dict_load);
+    letter_index_vs_scan(&dict);
+    packed_vs_unpacked(&dict);
+}
@@ -1,7 +1,10 @@
+use std::collections::HashMap;
 use std::fmt;
+use std::iter::FromIterator;
+use std::sync::Arc;
 use try_from::TryFrom;
 
-use unidecode::unidecode;
+use unidecode::{unidecode, unidecode_char};
 
 /// Letter
 /// really just a byte
@@ -9,6 +12,35 @@ use unidecode::unidecode;
 #[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct Letter(u8);
 
+impl Letter {
+    // the 26 letters from A to Z
+    pub fn all() -> impl Iterator<Item = Letter> {
+        (b'A'..=b'Z').map(Letter)
+    }
+
+    // the letter at the given position in the alphabet, with A at 0
+    // none past Z
+    pub fn from_index(index: usize) -> Option<Letter> {
+        if index < 26 {
+            Some(Letter(b'A' + index as u8))
+        } else {
+            None
+        }
+    }
+
+    // the letter's position in the alphabet, with A at 0 and Z at 25
+    // e.g. for indexing an array of counts per letter
+    pub fn to_index(self) -> usize {
+        (self.0 - b'A') as usize
+    }
+
+    // whether the letter is A, E, I, O or U
+    // Y counts as a consonant here, see VowelPolicy for words where it shouldn't
+    pub fn is_vowel(self) -> bool {
+        b"AEIOU".contains(&self.0)
+    }
+}
+
 impl TryFrom<u8> for Letter {
     type Err = ();
 
@@ -50,7 +82,7 @@ impl Word {
     // constructs a new word from the slice
     pub fn new(letters: &[Letter]) -> Word {
         Word {
-            letters: letters.iter().cloned().collect(),
+            letters: letters.to_vec(),
         }
     }
 
@@ -58,6 +90,80 @@ impl Word {
     pub fn size(&self) -> usize {
         self.letters.len()
     }
+
+    // the number of letters that have to be inserted, removed or replaced
+    // to turn this word into the other one, i.e. the levenshtein distance
+    pub fn edit_distance(&self, other: &Word) -> usize {
+        // prev[j] is the distance between the letters of self seen so far
+        // and the first j letters of other
+        let mut prev: Vec<usize> = (0..other.size() + 1).collect();
+        for (i, letter) in self.letters.iter().enumerate() {
+            let mut current = vec![i + 1; other.size() + 1];
+            for (j, other_letter) in other.letters.iter().enumerate() {
+                let replace = prev[j] + if letter == other_letter { 0 } else { 1 };
+                current[j + 1] = replace.min(prev[j + 1] + 1).min(current[j] + 1);
+            }
+            prev = current;
+        }
+        prev[other.size()]
+    }
+
+    // the word as a string in the given case
+    // letters are always stored as capitals, this only changes how they're shown
+    pub fn to_string_case(&self, case: LetterCase) -> String {
+        let upper = String::from(self);
+        match case {
+            LetterCase::Upper => upper,
+            LetterCase::Lower => upper.to_ascii_lowercase(),
+            LetterCase::Title => {
+                let (first, rest) = upper.split_at(upper.len().min(1));
+                first.to_string() + &rest.to_ascii_lowercase()
+            }
+        }
+    }
+
+    // the fraction of the letters that are vowels, 0 for an empty word
+    // the policy says when Y counts as one
+    pub fn vowel_ratio(&self, policy: VowelPolicy) -> f32 {
+        if self.letters.is_empty() {
+            return 0f32;
+        }
+        let vowels = (0..self.letters.len()).filter(|&pos| self.is_vowel_at(pos, policy)).count();
+        (vowels as f32) / (self.letters.len() as f32)
+    }
+
+    // how many of the word's letters are vowels, not counting Y
+    // e.g. 4 for AEROBIC
+    pub fn vowel_count(&self) -> usize {
+        self.letters.iter().filter(|letter| letter.is_vowel()).count()
+    }
+
+    // whether the letter at the position is a vowel
+    fn is_vowel_at(&self, pos: usize, policy: VowelPolicy) -> bool {
+        let letter = self.letters[pos];
+        if letter.0 != b'Y' {
+            return letter.is_vowel();
+        }
+        match policy {
+            VowelPolicy::YAlwaysVowel => true,
+            VowelPolicy::YNeverVowel => false,
+            VowelPolicy::YContextual => {
+                pos > 0 && !self.letters.get(pos + 1).is_some_and(|next| next.is_vowel())
+            }
+        }
+    }
+
+    // converts raw bytes to a word without requiring them to be utf-8
+    // every byte is read as latin-1, so e.g. 0xE9 (é) becomes E
+    // bytes that aren't letters are dropped
+    pub fn from_bytes_lossy(bytes: &[u8]) -> Word {
+        let letters = latin1_bytes(bytes)
+            .filter_map(|b| Letter::try_from(b).ok())
+            .collect();
+        Word {
+            letters,
+        }
+    }
 }
 
 impl<'a> From<&'a str> for Word {
@@ -69,7 +175,7 @@ impl<'a> From<&'a str> for Word {
             .filter_map(|b| Letter::try_from(b).ok())
             .collect();
         Word {
-            letters: letters,
+            letters,
         }
     }
 }
@@ -90,83 +196,492 @@ impl fmt::Display for Word {
     }
 }
 
+// LetterCase
+// how to capitalize a word when showing it, see Word::to_string_case
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LetterCase {
+    // CROSSWORD
+    Upper,
+    // crossword
+    Lower,
+    // Crossword
+    Title,
+}
+
+// VowelPolicy
+// when Y counts as a vowel, see Word::vowel_ratio
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum VowelPolicy {
+    // GYM, TOY and YES all have a vowel Y
+    #[default]
+    YAlwaysVowel,
+    // none of them do
+    YNeverVowel,
+    // Y is a consonant at the start of a word or before another vowel, as in YES or BEYOND,
+    // and a vowel anywhere else, as in GYM or TOY
+    YContextual,
+}
+
+// WordId
+// a word that's been interned in a WordPool
+// ids are only meaningful to the pool that handed them out
+
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct WordId(u32);
+
+// WordPool
+// stores every word once and hands out small copyable ids for them
+// so that long lists of words don't each need their own heap allocation
+// the list and the map share each word, and its packed form is kept alongside it
+
+#[derive(Clone, Debug, Default)]
+pub struct WordPool {
+    words: Vec<Arc<Word>>,
+    packed: Vec<PackedWord>,
+    ids: HashMap<Arc<Word>, WordId>,
+}
+
+impl WordPool {
+    pub fn new() -> WordPool {
+        WordPool::default()
+    }
+
+    // the id for the word, adding it to the pool if it isn't there yet
+    // the word is only cloned the first time it's seen
+    pub fn intern(&mut self, word: &Word) -> WordId {
+        if let Some(&id) = self.ids.get(word) {
+            return id;
+        }
+        let id = WordId(self.words.len() as u32);
+        let word = Arc::new(word.clone());
+        self.packed.push(word.packed());
+        self.words.push(word.clone());
+        self.ids.insert(word, id);
+        id
+    }
+
+    // the id for the word, if it's in the pool
+    pub fn id(&self, word: &Word) -> Option<WordId> {
+        self.ids.get(word).cloned()
+    }
+
+    // the word an id stands for
+    // panics if the id came from a different pool
+    pub fn get(&self, id: WordId) -> &Word {
+        &self.words[id.0 as usize]
+    }
+
+    // the word an id stands for, for storing somewhere else without copying it
+    pub fn get_shared(&self, id: WordId) -> Arc<Word> {
+        self.words[id.0 as usize].clone()
+    }
+
+    // the packed form of the word an id stands for, see Word::packed
+    pub(crate) fn packed(&self, id: WordId) -> PackedWord {
+        self.packed[id.0 as usize]
+    }
+
+    // check if the word an id stands for matches the pattern, using its packed form
+    pub(crate) fn matches(&self, id: WordId, pattern: &Pattern) -> bool {
+        pattern.packed().matches(self.get(id), self.packed(id))
+    }
+
+    // the number of words in the pool
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+}
+
+// LetterSet
+// a set of letters as one bit per letter, A in the lowest bit
+// so a pattern class holding one is cheap to copy, hash and compare
+
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct LetterSet(u32);
+
+impl LetterSet {
+    pub fn new() -> LetterSet {
+        LetterSet::default()
+    }
+
+    pub fn insert(&mut self, letter: Letter) {
+        self.0 |= 1 << letter.to_index();
+    }
+
+    pub fn contains(self, letter: Letter) -> bool {
+        self.0 & 1 << letter.to_index() != 0
+    }
+
+    // the letters in both sets
+    pub fn intersection(self, other: LetterSet) -> LetterSet {
+        LetterSet(self.0 & other.0)
+    }
+
+    // the letters in either set
+    pub fn union(self, other: LetterSet) -> LetterSet {
+        LetterSet(self.0 | other.0)
+    }
+
+    pub fn len(self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    // the letters in alphabetical order
+    pub fn iter(self) -> impl Iterator<Item = Letter> {
+        Letter::all().filter(move |&letter| self.contains(letter))
+    }
+}
+
+impl FromIterator<Letter> for LetterSet {
+    fn from_iter<I: IntoIterator<Item = Letter>>(letters: I) -> LetterSet {
+        let mut set = LetterSet::new();
+        for letter in letters {
+            set.insert(letter);
+        }
+        set
+    }
+}
+
+// PatternClass
+// the letters allowed at one position of a pattern
+
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum PatternClass {
+    // any letter, written .
+    Any,
+    // just this letter, written A
+    Exact(Letter),
+    // any of these letters, written [AEIOU]
+    OneOf(LetterSet),
+    // any letter but these, written [^S]
+    NoneOf(LetterSet),
+}
+
+impl PatternClass {
+    // check if the letter is allowed
+    pub fn matches(&self, letter: Letter) -> bool {
+        match *self {
+            PatternClass::Any => true,
+            PatternClass::Exact(l) => l == letter,
+            PatternClass::OneOf(letters) => letters.contains(letter),
+            PatternClass::NoneOf(letters) => !letters.contains(letter),
+        }
+    }
+
+    // the letter if only an exact letter is allowed
+    pub fn exact(&self) -> Option<Letter> {
+        match *self {
+            PatternClass::Exact(l) => Some(l),
+            _ => None,
+        }
+    }
+
+    // the class allowing only the letters both classes allow, none if there aren't any
+    fn merge(&self, other: &PatternClass) -> Option<PatternClass> {
+        let merged = match (*self, *other) {
+            (PatternClass::Any, class) | (class, PatternClass::Any) => class,
+            (PatternClass::Exact(l), class) | (class, PatternClass::Exact(l)) => {
+                if !class.matches(l) {
+                    return None;
+                }
+                PatternClass::Exact(l)
+            }
+            (PatternClass::OneOf(letters), class) | (class, PatternClass::OneOf(letters)) => {
+                let kept = letters.iter().filter(|&l| class.matches(l)).collect::<LetterSet>();
+                if kept.len() == 1 {
+                    PatternClass::Exact(kept.iter().next().unwrap())
+                } else {
+                    PatternClass::OneOf(kept)
+                }
+            }
+            (PatternClass::NoneOf(a), PatternClass::NoneOf(b)) => PatternClass::NoneOf(a.union(b)),
+        };
+        if Letter::all().any(|l| merged.matches(l)) {
+            Some(merged)
+        } else {
+            None
+        }
+    }
+}
+
+impl From<Option<Letter>> for PatternClass {
+    fn from(mask: Option<Letter>) -> PatternClass {
+        match mask {
+            Some(l) => PatternClass::Exact(l),
+            None => PatternClass::Any,
+        }
+    }
+}
+
+impl fmt::Display for PatternClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // the letters come out in alphabetical order, so a class always prints the same way
+        let letters = |letters: LetterSet| letters.iter().map(|l| l.to_string()).collect::<String>();
+        match *self {
+            PatternClass::Any => write!(f, "."),
+            PatternClass::Exact(l) => write!(f, "{}", l),
+            PatternClass::OneOf(set) => write!(f, "[{}]", letters(set)),
+            PatternClass::NoneOf(set) => write!(f, "[^{}]", letters(set)),
+        }
+    }
+}
+
 // Pattern
-// just a vector of option<letter>
+// just a vector of pattern classes
 // the pattern "..A." matches "STAN", for example
-// . is represented by none
-// A is represented by some(Letter(b'A'))
+// . is represented by any
+// A is represented by exact(Letter(b'A'))
+// [AEIOU] is represented by oneof and [^S] by noneof
+// the packed form is worked out once, when the pattern is made,
+// so matching never has to look at its wildcards
 
 #[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct Pattern {
-    pub masks: Vec<Option<Letter>>,
+    masks: Vec<PatternClass>,
+    packed: PackedPattern,
 }
 
 impl Pattern {
     // constructs a pattern from the given slice
+    // none is any letter and some is that exact letter
     pub fn new(masks: &[Option<Letter>]) -> Pattern {
+        Pattern::from_classes(masks.iter().map(|&mask| PatternClass::from(mask)).collect())
+    }
+
+    // constructs a pattern from a class for each letter
+    fn from_classes(masks: Vec<PatternClass>) -> Pattern {
+        let packed = PackedPattern::new(&masks);
         Pattern {
-            masks: masks.iter().cloned().collect(),
+            masks,
+            packed,
         }
     }
 
+    // the class for each letter of the pattern
+    pub fn masks(&self) -> &[PatternClass] {
+        &self.masks
+    }
+
     // the length of the pattern
     pub fn size(&self) -> usize {
         self.masks.len()
     }
 
+    // converts raw bytes to a pattern without requiring them to be utf-8
+    // works like from_bytes_lossy for words, except . is kept as an empty mask
+    pub fn from_bytes_lossy(bytes: &[u8]) -> Pattern {
+        let masks = latin1_bytes(bytes)
+            .filter_map(|b| {
+                if b == b'.' {
+                    Some(PatternClass::Any)
+                } else {
+                    Letter::try_from(b).ok().map(PatternClass::Exact)
+                }
+            })
+            .collect();
+        Pattern::from_classes(masks)
+    }
+
     // check if a word matches the pattern
+    // only the exact letters and classes are checked, the wildcards match anything
     pub fn matches(&self, word: &Word) -> bool {
+        let packed = &self.packed;
         // can't match if they're not the same size
-        if word.size() != self.size() {
-            false
-        // make sure every some(letter) matches the corresponding letter in the word
-        } else {
-            self.masks.iter()
-                .zip(word.letters.iter())
-                .filter_map(|(mask, letter)| mask.map(|l| l == *letter))
-                .all(|x| x)
+        word.size() == packed.size
+            && packed.exact.iter().all(|&(pos, letter)| word.letters[pos] == letter)
+            && packed.classes.iter().all(|&(pos, class)| class.matches(word.letters[pos]))
+    }
+
+    // the first position where the word doesn't match the pattern, none if it matches
+    // if the shorter of the two runs out first, that's where they differ,
+    // e.g. CAT against the pattern CA.S differs at 3
+    pub fn first_mismatch(&self, word: &Word) -> Option<usize> {
+        self.masks.iter()
+            .zip(word.letters.iter())
+            .position(|(mask, &letter)| !mask.matches(letter))
+            .or_else(|| if word.size() != self.size() { Some(word.size().min(self.size())) } else { None })
+    }
+
+    // how many positions are fixed to an exact letter
+    pub fn fixed_count(&self) -> usize {
+        self.masks.iter().filter(|mask| mask.exact().is_some()).count()
+    }
+
+    // how many positions allow any letter
+    // a class like [AEIOU] is neither fixed nor a wildcard
+    pub fn wildcard_count(&self) -> usize {
+        self.masks.iter().filter(|&mask| *mask == PatternClass::Any).count()
+    }
+
+    // the fixed letters with their positions, in order
+    // e.g. [(1, A), (3, Z)] for .A.Z
+    pub fn fixed_positions(&self) -> Vec<(usize, Letter)> {
+        self.masks.iter()
+            .enumerate()
+            .filter_map(|(pos, mask)| mask.exact().map(|letter| (pos, letter)))
+            .collect()
+    }
+
+    // the pattern matching exactly the words both patterns match
+    // e.g. C.. merged with .A[RT] is CA[RT]
+    // none if the patterns are different lengths or rule out every letter somewhere,
+    // e.g. by fixing different letters in the same position
+    pub fn merge(&self, other: &Pattern) -> Option<Pattern> {
+        if self.size() != other.size() {
+            return None;
         }
+        let masks = self.masks.iter()
+            .zip(other.masks.iter())
+            .map(|(a, b)| a.merge(b))
+            .collect::<Option<Vec<_>>>()?;
+        Some(Pattern::from_classes(masks))
     }
 }
 
 impl<'a> From<&'a str> for Pattern {
     // converts from a string to a pattern
-    // ignores all characters other than alphabetic ones and . (period)
-    // which represents an empty pattern
+    // ignores all characters other than alphabetic ones, . (period)
+    // which represents an empty pattern, and bracketed classes
     // e.g. "?.A.'" becomes the pattern ".A."
+    // and "[AEIOU].[^S]" matches a vowel, any letter, then anything but S
     fn from(string: &'a str) -> Pattern {
-        let masks: Vec<Option<Letter>> = unidecode(string).bytes()
-            .filter_map(|b| {
-                if b == b'.' {
-                    Some(None)
-                } else {
-                    Letter::try_from(b).ok().map(Some)
+        let mut masks = vec![];
+        let mut bytes = unidecode(string).into_bytes().into_iter().peekable();
+        while let Some(b) = bytes.next() {
+            if b == b'.' {
+                masks.push(PatternClass::Any);
+            } else if b == b'[' {
+                let negated = bytes.peek() == Some(&b'^');
+                if negated {
+                    bytes.next();
                 }
-            })
-            .collect();
-        Pattern {
-            masks: masks,
+                // an unclosed bracket runs to the end of the string
+                let letters = bytes.by_ref()
+                    .take_while(|&b| b != b']')
+                    .filter_map(|b| Letter::try_from(b).ok())
+                    .collect();
+                masks.push(if negated {
+                    PatternClass::NoneOf(letters)
+                } else {
+                    PatternClass::OneOf(letters)
+                });
+            } else if let Ok(letter) = Letter::try_from(b) {
+                masks.push(PatternClass::Exact(letter));
+            }
         }
+        Pattern::from_classes(masks)
     }
 }
 
 impl fmt::Display for Pattern {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        for opt in &self.masks {
-            match *opt {
-                Some(l) => write!(f, "{}", l),
-                None => write!(f, "."),
-            }?;
+        for mask in &self.masks {
+            write!(f, "{}", mask)?;
         }
         Ok(())
     }
 }
 
+// PackedWord
+// a word's letters packed into one number, five bits a letter,
+// so a packed pattern can check all of its exact letters with one comparison
+// only the first MAX_PACKED_LEN letters fit, see Word::packed
+
+pub(crate) const MAX_PACKED_LEN: usize = 25;
+
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub(crate) struct PackedWord(u128);
+
+impl Word {
+    // pack the word's letters, e.g. for a dictionary to store alongside it
+    // letters past MAX_PACKED_LEN are left out, the pattern checks those one at a time
+    pub(crate) fn packed(&self) -> PackedWord {
+        PackedWord(self.letters.iter()
+            .take(MAX_PACKED_LEN)
+            .enumerate()
+            .fold(0u128, |packed, (pos, letter)| packed | (letter.to_index() as u128 + 1) << (5 * pos)))
+    }
+}
+
+// PackedPattern
+// a pattern's exact letters as bits to compare a packed word against,
+// plus the positions that still have to be checked a letter at a time
+
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) struct PackedPattern {
+    size: usize,
+    // the bits the pattern fixes, i.e. every exact letter that fits
+    mask: u128,
+    // what those bits have to be
+    value: u128,
+    // the exact letters in mask, for matching words that aren't packed
+    exact: Vec<(usize, Letter)>,
+    // the positions that allow some letters but not others, like [AEIOU],
+    // and any exact letters too far in to pack
+    classes: Vec<(usize, PatternClass)>,
+}
+
+impl PackedPattern {
+    fn new(masks: &[PatternClass]) -> PackedPattern {
+        let mut packed = PackedPattern {
+            size: masks.len(),
+            ..PackedPattern::default()
+        };
+        for (pos, mask) in masks.iter().enumerate() {
+            match *mask {
+                PatternClass::Any => {}
+                PatternClass::Exact(letter) if pos < MAX_PACKED_LEN => {
+                    packed.mask |= 0x1F << (5 * pos);
+                    packed.value |= (letter.to_index() as u128 + 1) << (5 * pos);
+                    packed.exact.push((pos, letter));
+                }
+                class => packed.classes.push((pos, class)),
+            }
+        }
+        packed
+    }
+
+    // check if a word matches the pattern, given the word and its packed form
+    // the same as Pattern::matches, but the exact letters are checked all at once
+    pub(crate) fn matches(&self, word: &Word, packed: PackedWord) -> bool {
+        word.size() == self.size
+            && packed.0 & self.mask == self.value
+            && self.classes.iter().all(|&(pos, class)| class.matches(word.letters[pos]))
+    }
+}
+
+impl Pattern {
+    // the packed form of the pattern, for matching words whose packed form is already known
+    pub(crate) fn packed(&self) -> &PackedPattern {
+        &self.packed
+    }
+}
+
+// reads every byte as a latin-1 character and transliterates it to ascii bytes
+// characters that aren't letters are dropped first, except for ., so symbols
+// like × don't turn into letters, but accented letters like é still do
+fn latin1_bytes<'a>(bytes: &'a [u8]) -> impl Iterator<Item = u8> + 'a {
+    bytes.iter()
+        .map(|&b| b as char)
+        .filter(|&c| c.is_alphabetic() || c == '.')
+        .flat_map(|c| unidecode_char(c).bytes())
+}
+
 // GridCoord
 // a coordinate in a grid
 // just a pair of usize
 
-#[derive(Clone, Copy, Debug, Default, Eq, Ord, PartialEq, PartialOrd)]
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct GridCoord {
     pub row: usize,
     pub col: usize,
@@ -176,8 +691,8 @@ impl GridCoord {
     // constructs a new gridcoord
     pub fn new(row: usize, col: usize) -> GridCoord {
         GridCoord {
-            row: row,
-            col: col,
+            row,
+            col,
         }
     }
 
@@ -193,9 +708,11 @@ impl GridCoord {
     }
 
     // returns the cells neighboring the gridcoord
+    // in the order west, east, north, south
+    // neighbors with a negative row or column are left out
     pub fn neighbors(&self) -> Vec<GridCoord> {
-        [self.offset(0, -1), self.offset(1, 0), self.offset(0, -1), self.offset(-1, 0)]
-            .into_iter()
+        [self.offset(0, -1), self.offset(0, 1), self.offset(-1, 0), self.offset(1, 0)]
+            .iter()
             .filter_map(|opt| *opt)
             .collect()
     }
@@ -286,7 +803,7 @@ pub struct Entry {
 impl Entry {
     pub fn new(letters: Vec<Option<Letter>>) -> Entry {
         Entry {
-            letters: letters,
+            letters,
         }
     }
 }
@@ -304,10 +821,7 @@ pub enum Cell {
 
 impl Cell {
     pub fn is_white(&self) -> bool {
-        match *self {
-            Cell::White(_) => true,
-            _ => false,
-        }
+        matches!(*self, Cell::White(_))
     }
 
     pub fn is_black(&self) -> bool {
@@ -315,9 +829,234 @@ impl Cell {
     }
 
     pub fn is_filled(&self) -> bool {
-        match *self {
-            Cell::White(Some(_)) => true,
-            _ => false,
+        matches!(*self, Cell::White(Some(_)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::{BTreeMap, HashSet};
+
+    #[test]
+    fn all_letters_run_from_a_to_z() {
+        let letters = Letter::all().collect::<Vec<_>>();
+        assert_eq!(letters.len(), 26);
+        assert_eq!(letters[0], Letter(b'A'));
+        assert_eq!(letters[25], Letter(b'Z'));
+        for (index, &letter) in letters.iter().enumerate() {
+            assert_eq!(letter.to_index(), index);
+            assert_eq!(Letter::from_index(index), Some(letter));
+        }
+        assert_eq!(Letter::from_index(26), None);
+    }
+
+    #[test]
+    fn edit_distance_of_known_pairs() {
+        let distance = |a: &str, b: &str| Word::from(a).edit_distance(&Word::from(b));
+        assert_eq!(distance("KITTEN", "SITTING"), 3);
+        assert_eq!(distance("FLAW", "LAWN"), 2);
+        assert_eq!(distance("CAT", "CAT"), 0);
+        assert_eq!(distance("", "DOG"), 3);
+        assert_eq!(distance("DOG", ""), 3);
+        assert_eq!(distance("CAT", "COT"), 1);
+        assert_eq!(distance("SUNDAY", "SATURDAY"), 3);
+    }
+
+    #[test]
+    fn vowels_are_aeiou() {
+        for &b in b"AEIOU" {
+            assert!(Letter(b).is_vowel());
+        }
+        assert!(!Letter(b'B').is_vowel());
+        assert!(!Letter(b'Y').is_vowel());
+        assert_eq!(Word::from("AEROBIC").vowel_count(), 4);
+        assert_eq!(Word::from("GYM").vowel_count(), 0);
+    }
+
+    #[test]
+    fn vowel_ratio_follows_the_y_policy() {
+        let ratio = |word: &str, policy| Word::from(word).vowel_ratio(policy);
+        assert_eq!(ratio("GYM", VowelPolicy::YAlwaysVowel), 1.0 / 3.0);
+        assert_eq!(ratio("GYM", VowelPolicy::YNeverVowel), 0.0);
+        assert_eq!(ratio("GYM", VowelPolicy::YContextual), 1.0 / 3.0);
+        assert_eq!(ratio("YES", VowelPolicy::YAlwaysVowel), 2.0 / 3.0);
+        assert_eq!(ratio("YES", VowelPolicy::YContextual), 1.0 / 3.0);
+        assert_eq!(ratio("BEYOND", VowelPolicy::YAlwaysVowel), 0.5);
+        assert_eq!(ratio("BEYOND", VowelPolicy::YNeverVowel), 2.0 / 6.0);
+        assert_eq!(ratio("BEYOND", VowelPolicy::YContextual), 2.0 / 6.0);
+        assert_eq!(ratio("", VowelPolicy::default()), 0.0);
+        assert_eq!(VowelPolicy::default(), VowelPolicy::YAlwaysVowel);
+    }
+
+    #[test]
+    fn to_string_case_changes_only_the_rendering() {
+        let word = Word::from("crossword");
+        assert_eq!(word.to_string_case(LetterCase::Upper), "CROSSWORD");
+        assert_eq!(word.to_string_case(LetterCase::Lower), "crossword");
+        assert_eq!(word.to_string_case(LetterCase::Title), "Crossword");
+        assert_eq!(word.to_string(), "CROSSWORD");
+
+        let single = Word::from("a");
+        assert_eq!(single.to_string_case(LetterCase::Title), "A");
+        assert_eq!(single.to_string_case(LetterCase::Lower), "a");
+        assert_eq!(Word::default().to_string_case(LetterCase::Title), "");
+    }
+
+    #[test]
+    fn word_pool_interns_each_word_once() {
+        let mut pool = WordPool::new();
+        let cat = pool.intern(&Word::from("CAT"));
+        let dog = pool.intern(&Word::from("DOG"));
+        assert_ne!(cat, dog);
+        assert_eq!(pool.intern(&Word::from("CAT")), cat);
+        assert_eq!(pool.len(), 2);
+        assert_eq!(pool.get(dog), &Word::from("DOG"));
+        assert_eq!(pool.packed(dog), Word::from("DOG").packed());
+        assert_eq!(pool.id(&Word::from("DOG")), Some(dog));
+        assert_eq!(pool.id(&Word::from("COW")), None);
+    }
+
+    #[test]
+    fn from_bytes_lossy_reads_latin1() {
+        // "café!" in latin-1
+        assert_eq!(Word::from_bytes_lossy(b"caf\xe9!"), Word::from("CAFE"));
+        assert_eq!(Pattern::from_bytes_lossy(b"c.f\xe9-"), Pattern::from("C.FE"));
+        // bytes that aren't letters are dropped, including symbols like ×
+        assert_eq!(Word::from_bytes_lossy(b"\x00a\xd7b"), Word::from("AB"));
+        assert_eq!(Pattern::from_bytes_lossy(b"a.\xf7\xdf"), Pattern::from("A.SS"));
+    }
+
+    #[test]
+    fn first_mismatch_finds_where_a_word_differs() {
+        let pattern = Pattern::from("C.[AEIOU]T");
+        assert_eq!(pattern.first_mismatch(&Word::from("CHAT")), None);
+        assert_eq!(pattern.first_mismatch(&Word::from("CHRT")), Some(2));
+        assert_eq!(pattern.first_mismatch(&Word::from("BOAT")), Some(0));
+        // running out counts as differing
+        assert_eq!(pattern.first_mismatch(&Word::from("CHA")), Some(3));
+        assert_eq!(pattern.first_mismatch(&Word::from("CHATS")), Some(4));
+        assert_eq!(pattern.first_mismatch(&Word::from("CHRTS")), Some(2));
+    }
+
+    #[test]
+    fn packed_patterns_match_like_patterns() {
+        let words = ["CAT", "COT", "CUT", "CATS", "ZZZ", "", "ABCDEFGHIJKLMNO", "ABCDEFGHIJKLMNOP"]
+            .iter()
+            .map(|word| Word::from(*word))
+            .collect::<Vec<_>>();
+        let patterns = ["C.T", "...", "C[AO]T", "[^C]..", ".A..", "", "ABCDEFGHIJKLMN.", "Z.Z", "..............."];
+        for pattern in &patterns {
+            let pattern = Pattern::from(*pattern);
+            for word in &words {
+                assert_eq!(pattern.packed().matches(word, word.packed()), pattern.matches(word), "{} against {}", word, pattern);
+            }
+        }
+        // letters past MAX_PACKED_LEN are still checked
+        let long = Word::from("ABCDEFGHIJKLMNOPQRSTUVWXYZ");
+        let mut other = long.clone();
+        other.letters[25] = Letter(b'A');
+        for pattern in &["ABCDEFGHIJKLMNOPQRSTUVWXYZ", ".........................Z", "........................[XY]Z"] {
+            let pattern = Pattern::from(*pattern);
+            assert!(pattern.matches(&long), "{}", pattern);
+            assert!(pattern.packed().matches(&long, long.packed()), "{}", pattern);
+            assert!(!pattern.matches(&other), "{}", pattern);
+            assert!(!pattern.packed().matches(&other, other.packed()), "{}", pattern);
+        }
+    }
+
+    #[test]
+    fn fixed_and_wildcard_positions_are_counted() {
+        let pattern = Pattern::from(".A.Z");
+        assert_eq!(pattern.fixed_count(), 2);
+        assert_eq!(pattern.wildcard_count(), 2);
+        assert_eq!(pattern.fixed_positions(), vec![(1, Letter(b'A')), (3, Letter(b'Z'))]);
+
+        // classes are neither
+        let pattern = Pattern::from("[AE].Z");
+        assert_eq!(pattern.fixed_count(), 1);
+        assert_eq!(pattern.wildcard_count(), 1);
+        assert_eq!(pattern.fixed_positions(), vec![(2, Letter(b'Z'))]);
+    }
+
+    #[test]
+    fn merging_patterns_keeps_both_constraints() {
+        let merge = |a: &str, b: &str| Pattern::from(a).merge(&Pattern::from(b));
+        assert_eq!(merge("C..", ".A."), Some(Pattern::from("CA.")));
+        assert_eq!(merge("C..", ".A[RT]"), Some(Pattern::from("CA[RT]")));
+        assert_eq!(merge("[AEIOU]..", "[^AE].."), Some(Pattern::from("[IOU]..")));
+        assert_eq!(merge("[AB].", "[BC]."), Some(Pattern::from("B.")));
+        assert_eq!(merge("[^A].", "[^B]."), Some(Pattern::from("[^AB].")));
+
+        // conflicting letters
+        assert_eq!(merge("C..", "B.."), None);
+        assert_eq!(merge("C..", "[^C].."), None);
+        assert_eq!(merge("[AB].", "[CD]."), None);
+
+        // different lengths
+        assert_eq!(merge("C..", "C..."), None);
+    }
+
+    #[test]
+    fn pattern_classes_match_their_letters() {
+        let cat = Word::from("CAT");
+        let cot = Word::from("COT");
+        let cut = Word::from("CUT");
+        let cab = Word::from("CAB");
+
+        // an exact letter only matches itself, a class matches any of its letters
+        let exact = Pattern::from("CAT");
+        let one_of = Pattern::from("C[AO]T");
+        assert!(exact.matches(&cat) && one_of.matches(&cat));
+        assert!(!exact.matches(&cot) && one_of.matches(&cot));
+        assert!(!one_of.matches(&cut));
+
+        let none_of = Pattern::from("C[^A].");
+        assert!(none_of.matches(&cot) && none_of.matches(&cut));
+        assert!(!none_of.matches(&cat) && !none_of.matches(&cab));
+
+        let any = Pattern::from("C..");
+        assert!([&cat, &cot, &cut, &cab].iter().all(|w| any.matches(w)));
+
+        assert_eq!(one_of.masks[1], PatternClass::OneOf(vec![Letter(b'A'), Letter(b'O')].into_iter().collect()));
+        assert_eq!(none_of.masks[2], PatternClass::Any);
+        assert_eq!(Pattern::from("[uoiea].t").to_string(), "[AEIOU].T");
+        assert_eq!(none_of.to_string(), "C[^A].");
+        // lowercase and stray characters inside brackets are handled like everywhere else
+        assert_eq!(Pattern::from("c[a, o]-t"), one_of);
+        assert_eq!(Pattern::new(&[Some(Letter(b'C')), None]), Pattern::from("C."));
+
+        // patterns can be map keys, classes and all
+        let mut counts = BTreeMap::new();
+        for pattern in &[&one_of, &none_of, &any, &Pattern::from("C[OA]T")] {
+            *counts.entry((*pattern).clone()).or_insert(0) += 1;
         }
+        assert_eq!(counts.len(), 3);
+        assert_eq!(counts[&one_of], 2);
+        assert_eq!(counts.keys().collect::<HashSet<_>>().len(), 3);
+    }
+
+    #[test]
+    fn letter_sets_hold_each_letter_once() {
+        let set = "QUIZ".bytes().chain("QUAY".bytes()).map(Letter).collect::<LetterSet>();
+        assert_eq!(set.len(), 6);
+        assert_eq!(set.iter().map(|l| l.to_string()).collect::<String>(), "AIQUYZ");
+        assert!(set.contains(Letter(b'Z')) && !set.contains(Letter(b'B')));
+        let vowels = "AEIOU".bytes().map(Letter).collect::<LetterSet>();
+        assert_eq!(set.intersection(vowels).len(), 3);
+        assert_eq!(set.union(vowels).len(), 8);
+        assert!(LetterSet::new().is_empty());
+    }
+
+    #[test]
+    fn neighbors_are_orthogonal() {
+        assert_eq!(GridCoord::new(2, 3).neighbors(),
+                   vec![GridCoord::new(2, 2), GridCoord::new(2, 4), GridCoord::new(1, 3), GridCoord::new(3, 3)]);
+        // the west neighbor is clipped on the left edge
+        assert_eq!(GridCoord::new(2, 0).neighbors(),
+                   vec![GridCoord::new(2, 1), GridCoord::new(1, 0), GridCoord::new(3, 0)]);
+        // the west and north neighbors are clipped in the corner
+        assert_eq!(GridCoord::new(0, 0).neighbors(),
+                   vec![GridCoord::new(0, 1), GridCoord::new(1, 0)]);
     }
 }
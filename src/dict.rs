@@ -1,26 +1,90 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs::File;
+use std::hash::Hash;
 use std::io::prelude::*;
 use std::io;
+use std::ops::Bound;
 use std::path::Path;
+use std::rc::Rc;
+use std::sync::Arc;
 
 use basic_types::*;
 
-pub trait UnrankedDict: Sized {
+// DictLookup
+// finding words in a dictionary without changing it, which is all a solver needs
+// every dictionary stores its words once, in a WordPool, and its indices hold ids
+// lookups hand out those ids, so a solver can keep lists of them without cloning any words
+// a removed word keeps its id, so ids handed out earlier still stand for the same word,
+// and adding it back gives it the same id again
+pub trait DictLookup {
+    // the word an id from this dictionary stands for
+    fn word(&self, id: WordId) -> &Word;
+    // the id of the word, if it's in the dictionary
+    fn word_id(&self, word: &Word) -> Option<WordId>;
+    // iterate over the ids of the words matching the pattern
+    fn lookup_ids(&self, pattern: &Pattern) -> impl Iterator<Item = WordId>;
+
+    // check if the word an id from this dictionary stands for matches the pattern
+    fn id_matches(&self, id: WordId, pattern: &Pattern) -> bool {
+        pattern.matches(self.word(id))
+    }
+
+    // check if the dictionary contains a word
+    fn contains(&self, word: &Word) -> bool {
+        self.word_id(word).is_some()
+    }
+
+    // iterate over the words matching the pattern without cloning them
+    fn lookup_iter<'a>(&'a self, pattern: &Pattern) -> impl Iterator<Item = &'a Word> {
+        self.lookup_ids(pattern).map(move |id| self.word(id))
+    }
+
+    // find all words in the dictionary that match the pattern
+    fn lookup(&self, pattern: &Pattern) -> Vec<Word> {
+        self.lookup_iter(pattern).cloned().collect()
+    }
+
+    // how many words in the dictionary match the pattern, without collecting them
+    fn count_matches(&self, pattern: &Pattern) -> usize {
+        self.lookup_ids(pattern).count()
+    }
+}
+
+// RankedDictLookup
+// the lookups a ranked solve needs on top of DictLookup
+
+pub trait RankedDictLookup: DictLookup {
+    // the score of the word an id from this dictionary stands for, if it's still in it
+    fn score(&self, id: WordId) -> Option<i32>;
+    // the ids of the words matching the pattern scoring between lower and upper, inclusive
+    fn lookup_range_ids(&self, pattern: &Pattern, lower: Option<i32>, upper: Option<i32>) -> Vec<WordId>;
+    fn max_rank(&self) -> i32;
+    fn min_rank(&self) -> i32;
+
+    fn get_score(&self, word: &Word) -> Option<i32> {
+        self.word_id(word).and_then(|id| self.score(id))
+    }
+
+    // the words matching the pattern scoring between lower and upper, inclusive
+    fn lookup_range(&self, pattern: &Pattern, lower: Option<i32>, upper: Option<i32>) -> Vec<Word> {
+        self.lookup_range_ids(pattern, lower, upper).into_iter()
+            .map(|id| self.word(id).clone())
+            .collect()
+    }
+}
+
+// UnrankedDict and RankedDict
+// dictionaries that can be loaded and changed, as well as looked up in
+
+pub trait UnrankedDict: DictLookup + Sized {
     fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self>;
     fn add(&mut self, word: &Word);
     fn remove(&mut self, word: &Word);
-    fn contains(&self, word: &Word) -> bool;
-    fn lookup(&self, pattern: &Pattern) -> Vec<Word>;
 }
 
-pub trait RankedDict : UnrankedDict {
+pub trait RankedDict: UnrankedDict + RankedDictLookup {
     fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self>;
-    fn get_score(&self, word: &Word) -> Option<i32>;
     fn set_score(&mut self, word: &Word, rank: i32) -> bool;
-    fn lookup_range(&self, pattern: &Pattern, lower: Option<i32>, upper: Option<i32>) -> Vec<Word>;
-    fn max_rank(&self) -> i32;
-    fn min_rank(&self) -> i32;
 }
 
 // Dictionary
@@ -28,14 +92,183 @@ pub trait RankedDict : UnrankedDict {
 
 #[derive(Clone, Debug, Default)]
 pub struct Dictionary {
+    // every word that's been in the dictionary, along with its packed form,
+    // so lookups can match it without going letter by letter
+    pool: WordPool,
     // a map of word length to all words of that length
-    words_by_size: HashMap<usize, HashSet<Word>>,
+    words_by_size: HashMap<usize, HashSet<WordId>>,
+    // a map of (word length, position, letter) to the words of that length
+    // with that letter at that position
+    // lookups with fixed letters only have to check the smallest of these lists,
+    // at the cost of an id per letter of every word
+    // add already keeps words unique, so these are lists rather than sets
+    // and loading doesn't have to hash every word again for each of its letters
+    words_by_letter: HashMap<(usize, usize, Letter), Vec<WordId>>,
+    // a map of a word's letters in sorted order to all words with those letters
+    words_by_anagram: HashMap<Vec<Letter>, HashSet<WordId>>,
+    // how words loaded with punctuation or spaces were written, e.g. OCLOCK was O'CLOCK
+    display_forms: HashMap<WordId, String>,
 }
 
 impl Dictionary {
     pub fn new() -> Dictionary {
         Dictionary::default()
     }
+
+    // build a dictionary from words that are already in memory
+    pub fn from_words<I: IntoIterator<Item = Word>>(words: I) -> Dictionary {
+        let mut dict = Dictionary::new();
+        for word in words {
+            dict.add(&word);
+        }
+        dict
+    }
+
+    // the total number of words in the dictionary
+    pub fn len(&self) -> usize {
+        self.words_by_size.values().map(|set| set.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // the number of words of each length, sorted by length
+    // lengths without any words are left out
+    pub fn length_histogram(&self) -> Vec<(usize, usize)> {
+        let mut histogram = self.words_by_size.iter()
+            .map(|(&size, set)| (size, set.len()))
+            .filter(|&(_, count)| count > 0)
+            .collect::<Vec<_>>();
+        histogram.sort();
+        histogram
+    }
+
+    // the word as it was written in the file it was loaded from, e.g. O'CLOCK for OCLOCK
+    // words without punctuation are just shown as they are
+    pub fn display_form(&self, word: &Word) -> String {
+        self.word_id(word)
+            .and_then(|id| self.display_forms.get(&id))
+            .cloned()
+            .unwrap_or_else(|| word.to_string())
+    }
+
+    // all words made of exactly the given letters, in any order
+    // repeated letters have to be used as many times as they're given
+    pub fn anagrams(&self, letters: &[Letter]) -> Vec<Word> {
+        let mut words = self.words_by_anagram.get(&sorted_letters(letters))
+            .map(|set| set.iter().map(|&id| self.pool.get(id).clone()).collect::<Vec<_>>())
+            .unwrap_or_default();
+        words.sort();
+        words
+    }
+
+    // all words with the given letters somewhere in them, in order and next to each other,
+    // sorted alphabetically
+    // e.g. SCATTER and CAT both contain CAT
+    // every word contains the empty word
+    pub fn containing(&self, infix: &Word) -> Vec<Word> {
+        let mut words = self.infix_candidates(infix)
+            .filter(|word| infix.size() == 0 || word.letters.windows(infix.size()).any(|window| window == &infix.letters[..]))
+            .cloned()
+            .collect::<Vec<_>>();
+        words.sort();
+        words
+    }
+
+    // the words that might contain the infix
+    // for now that's every word long enough to, but a suffix index could narrow it down
+    fn infix_candidates<'a>(&'a self, infix: &Word) -> impl Iterator<Item = &'a Word> {
+        let min_size = infix.size().max(1);
+        self.words_by_size.iter()
+            .filter(move |&(&size, _)| size >= min_size)
+            .flat_map(move |(_, set)| set.iter().map(move |&id| self.pool.get(id)))
+    }
+
+    // every word in the dictionary
+    fn words(&self) -> impl Iterator<Item = &Word> {
+        self.words_by_size.values().flat_map(move |set| set.iter().map(move |&id| self.pool.get(id)))
+    }
+
+    // the k words in the dictionary closest to the given word by edit distance,
+    // closest first, with ties in alphabetical order
+    // useful for suggesting a real word when a hand-filled entry isn't one
+    pub fn nearest(&self, word: &Word, k: usize) -> Vec<Word> {
+        let mut distances = self.words()
+            .map(|other| (word.edit_distance(other), other))
+            .collect::<Vec<_>>();
+        distances.sort();
+        distances.into_iter()
+            .take(k)
+            .map(|(_, other)| other.clone())
+            .collect()
+    }
+
+    // summarizes the dictionary as a json object, e.g.
+    // {"words": 3, "lengths": {"3": 2, "5": 1}}
+    pub fn stats_json(&self) -> String {
+        format!("{{\"words\": {}, \"lengths\": {}}}", self.len(), histogram_json(&self.length_histogram()))
+    }
+
+    // how many words there are of each length and how often each letter is used
+    // e.g. for checking a word list has enough long words for a grid before solving it
+    pub fn stats(&self) -> DictStats {
+        let mut letter_counts = HashMap::new();
+        for word in self.words() {
+            for &letter in &word.letters {
+                *letter_counts.entry(letter).or_insert(0) += 1;
+            }
+        }
+        let mut letter_counts = letter_counts.into_iter().collect::<Vec<_>>();
+        letter_counts.sort_by(|&(a, a_count), &(b, b_count)| b_count.cmp(&a_count).then(a.cmp(&b)));
+        DictStats {
+            words: self.len(),
+            lengths: self.length_histogram(),
+            letter_counts,
+        }
+    }
+
+    // the words in both dictionaries
+    pub fn intersect(&self, other: &Dictionary) -> Dictionary {
+        self.filtered(|word| other.contains(word))
+    }
+
+    // the words in this dictionary that aren't in the other one
+    pub fn difference(&self, other: &Dictionary) -> Dictionary {
+        self.filtered(|word| !other.contains(word))
+    }
+
+    // a new dictionary with just the words that pass the filter
+    fn filtered<F: Fn(&Word) -> bool>(&self, keep: F) -> Dictionary {
+        let mut dict = Dictionary::new();
+        for &id in self.words_by_size.values().flatten() {
+            let word = self.pool.get(id);
+            if keep(word) {
+                let new_id = dict.insert(word);
+                if let Some(form) = self.display_forms.get(&id) {
+                    dict.display_forms.insert(new_id, form.clone());
+                }
+            }
+        }
+        dict
+    }
+
+    // add the word if it isn't here yet, returning its id either way
+    fn insert(&mut self, word: &Word) -> WordId {
+        let id = self.pool.intern(word);
+        if !self.words_by_size.entry(word.size()).or_default().insert(id) {
+            return id;
+        }
+        for (pos, &letter) in word.letters.iter().enumerate() {
+            self.words_by_letter.entry((word.size(), pos, letter))
+                .or_default()
+                .push(id);
+        }
+        self.words_by_anagram.entry(sorted_letters(&word.letters))
+            .or_default()
+            .insert(id);
+        id
+    }
 }
 
 impl UnrankedDict for Dictionary {
@@ -46,108 +279,290 @@ impl UnrankedDict for Dictionary {
 
         // split the file into words and add them to the dict
         let mut dict = Dictionary::new();
-        for word in entire.split('\n').map(Word::from) {
-            dict.add(&word);
+        for line in entire.split('\n') {
+            let word = Word::from(line);
+            let id = dict.insert(&word);
+            if let Some(form) = display_form_of(line, &word) {
+                dict.display_forms.insert(id, form);
+            }
         }
         Ok(dict)
     }
 
     // add a word to the dictionary
     fn add(&mut self, word: &Word) {
-        self.words_by_size.entry(word.size())
-            .or_insert(HashSet::new())
-            .insert(word.clone());
+        self.insert(word);
     }
 
     // remove a word from the dictionary
+    // sets and lists left empty are dropped, so removing words doesn't leave the indices cluttered
     fn remove(&mut self, word: &Word) {
-        self.words_by_size.entry(word.size())
-            .or_insert(HashSet::new())
-            .remove(word);
+        let id = match self.word_id(word) {
+            Some(id) => id,
+            None => return,
+        };
+        remove_from(&mut self.words_by_size, word.size(), id);
+        for (pos, &letter) in word.letters.iter().enumerate() {
+            let key = (word.size(), pos, letter);
+            if let Some(list) = self.words_by_letter.get_mut(&key) {
+                if let Some(i) = list.iter().position(|&other| other == id) {
+                    list.swap_remove(i);
+                }
+                if list.is_empty() {
+                    self.words_by_letter.remove(&key);
+                }
+            }
+        }
+        remove_from(&mut self.words_by_anagram, sorted_letters(&word.letters), id);
+        self.display_forms.remove(&id);
     }
+}
 
-    // check if the dictionary contains a word
-    fn contains(&self, word: &Word) -> bool {
-        match self.words_by_size.get(&word.size()) {
-            Some(set) => set.contains(word),
-            None => false
-        }
+impl DictLookup for Dictionary {
+    fn word(&self, id: WordId) -> &Word {
+        self.pool.get(id)
     }
 
-    // find all words in the dictionary that match the Pattern
-    fn lookup(&self, pattern: &Pattern) -> Vec<Word> {
+    fn word_id(&self, word: &Word) -> Option<WordId> {
+        let set = self.words_by_size.get(&word.size())?;
+        self.pool.id(word).filter(|id| set.contains(id))
+    }
+
+    // iterate over all words in the dictionary that match the Pattern
+    fn lookup_ids(&self, pattern: &Pattern) -> impl Iterator<Item = WordId> {
         // a blank pattern matches every word of that length
-        let empty = !pattern.masks.iter().any(|opt| opt.is_some());
-        if empty {
-            self.words_by_size[&pattern.size()].iter().cloned().collect()
-        }
+        let empty = pattern.wildcard_count() == pattern.size();
+        // with fixed letters only the words sharing the rarest of them can match
+        // if no word has one of its letters, nothing matches
+        // without any, every word of that length has to be checked
+        let rarest = pattern.fixed_positions().into_iter()
+            .map(|(pos, letter)| self.words_by_letter.get(&(pattern.size(), pos, letter)))
+            .min_by_key(|list| list.map_or(0, |list| list.len()));
+        let (by_letter, by_size) = match rarest {
+            Some(list) => (list, None),
+            None => (None, self.words_by_size.get(&pattern.size())),
+        };
+        let pool = &self.pool;
+        by_letter.into_iter().flat_map(|list| list.iter())
+            .chain(by_size.into_iter().flat_map(|set| set.iter()))
+            .cloned()
+            .filter(move |&id| empty || pool.matches(id, pattern))
+    }
 
-        // actually do the lookup
-        else {
-            self.words_by_size[&pattern.size()].iter()
-                .filter(|w| pattern.matches(w))
-                .cloned()
-                .collect()
+    fn id_matches(&self, id: WordId, pattern: &Pattern) -> bool {
+        self.pool.matches(id, pattern)
+    }
+
+    // a pattern with at most one exact letter and no classes matches
+    // a whole set in one of the indices, so there's nothing to count
+    fn count_matches(&self, pattern: &Pattern) -> usize {
+        let fixed = pattern.fixed_positions();
+        if fixed.len() + pattern.wildcard_count() == pattern.size() {
+            match fixed.as_slice() {
+                [] => return self.words_by_size.get(&pattern.size()).map_or(0, |set| set.len()),
+                [(pos, letter)] => {
+                    return self.words_by_letter.get(&(pattern.size(), *pos, *letter)).map_or(0, |set| set.len());
+                }
+                _ => {}
+            }
         }
+        self.lookup_ids(pattern).count()
     }
 }
 
+// DictStats
+// a summary of what's in a dictionary, see Dictionary::stats
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct DictStats {
+    // the total number of words
+    pub words: usize,
+    // the number of words of each length, sorted by length
+    pub lengths: Vec<(usize, usize)>,
+    // how many times each letter is used across all the words, most used first
+    // letters that aren't used at all are left out
+    pub letter_counts: Vec<(Letter, usize)>,
+}
+
 #[derive(Clone, Debug, Default)]
 pub struct RankedDictionary {
-    words_by_size: HashMap<usize, HashMap<Word, i32>>,
+    // every word that's been in the dictionary, along with its packed form,
+    // so lookups can match it without going letter by letter
+    pool: WordPool,
+    // the score of every word in the dictionary, removed words have none
+    scores: HashMap<WordId, i32>,
+    // the words of each length sorted by score, then alphabetically,
+    // so range lookups can skip words outside the range without looking at them
+    // the keys share their words with the pool
+    by_score: HashMap<usize, BTreeMap<(i32, Arc<Word>), WordId>>,
     default_score: i32,
+    // how words loaded with punctuation or spaces were written, e.g. OCLOCK was O'CLOCK
+    display_forms: HashMap<WordId, String>,
 }
 
 impl RankedDictionary {
     pub fn new() -> RankedDictionary {
         RankedDictionary::default()
     }
-}
 
-impl UnrankedDict for RankedDictionary {
-    fn from_file<P: AsRef<Path>>(path: P) -> io::Result<RankedDictionary> {
+    // build a dictionary from words that are already in memory, all with the default score
+    pub fn from_words<I: IntoIterator<Item = Word>>(words: I) -> RankedDictionary {
+        let mut dict = RankedDictionary::new();
+        for word in words {
+            dict.add(&word);
+        }
+        dict
+    }
+
+    // build a dictionary from words and their scores that are already in memory
+    pub fn from_scored_words<I: IntoIterator<Item = (Word, i32)>>(words: I) -> RankedDictionary {
+        let mut dict = RankedDictionary::new();
+        for (word, score) in words {
+            dict.insert_scored(&word, score);
+        }
+        dict
+    }
+
+    // load a dictionary from a file of words and scores, one word<delim>score per line,
+    // e.g. with ',' for a two column csv or '\t' for tab separated values
+    // bare words without a score get the default score, so partly scored lists load fully
+    // lines with more than one delimiter are skipped, and words whose score isn't
+    // a number get the default score, with a warning either way
+    pub fn from_file_with_delim<P: AsRef<Path>>(path: P, delim: char) -> io::Result<RankedDictionary> {
         // read the file
         let mut entire = String::new();
         File::open(path)?.read_to_string(&mut entire)?;
 
-        // split the file into words and add them to the dict
-        let mut dict = Self::new();
-        for word in entire.split('\n').map(Word::from) {
-            dict.add(&word);
+        // split the file into words and scores and add them to the dict
+        let mut dict = RankedDictionary::new();
+        let default_score = dict.default_score;
+        for (i, line) in entire.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let parts = line.split(delim).collect::<Vec<_>>();
+            if parts.len() > 2 {
+                warn!("skipping line {} of the dictionary, it isn't a word and a score: {:?}", i + 1, line);
+                continue;
+            }
+            let word = Word::from(parts[0]);
+            let id = match parts.get(1).map(|score| score.trim().parse::<i32>()) {
+                None => dict.insert_scored(&word, default_score),
+                Some(Ok(score)) => dict.insert_scored(&word, score),
+                Some(Err(_)) => {
+                    warn!("line {} of the dictionary has no valid score, using the default: {:?}", i + 1, line);
+                    dict.insert_scored(&word, default_score)
+                }
+            };
+            if let Some(form) = display_form_of(parts[0], &word) {
+                dict.display_forms.insert(id, form);
+            }
         }
         Ok(dict)
     }
 
-    fn add(&mut self, word: &Word) {
-        self.words_by_size.entry(word.size())
-            .or_insert(HashMap::new())
-            .insert(word.clone(), self.default_score);
+    // add the word with the given score, replacing its old score if it's already here
+    fn insert_scored(&mut self, word: &Word, score: i32) -> WordId {
+        let id = self.pool.intern(word);
+        let index = self.by_score.entry(word.size()).or_default();
+        let shared = self.pool.get_shared(id);
+        if let Some(prev_score) = self.scores.insert(id, score) {
+            index.remove(&(prev_score, shared.clone()));
+        }
+        index.insert((score, shared), id);
+        id
     }
 
-    fn remove(&mut self, word: &Word) {
-        self.words_by_size.entry(word.size())
-            .or_insert(HashMap::new())
-            .remove(word);
+    // the word as it was written in the file it was loaded from, e.g. O'CLOCK for OCLOCK
+    // words without punctuation are just shown as they are
+    pub fn display_form(&self, word: &Word) -> String {
+        self.word_id(word)
+            .and_then(|id| self.display_forms.get(&id))
+            .cloned()
+            .unwrap_or_else(|| word.to_string())
     }
 
-    fn contains(&self, word: &Word) -> bool {
-        match self.words_by_size.get(&word.size()) {
-            Some(map) => map.contains_key(word),
-            None => false,
+    // replace every word's score with f(word, score)
+    // e.g. |word, score| if word.size() == 3 { score - 20 } else { score } to demote short words
+    pub fn adjust_scores<F: Fn(&Word, i32) -> i32>(&mut self, f: F) {
+        for index in self.by_score.values_mut() {
+            let adjusted = index.iter()
+                .map(|((score, word), &id)| ((f(word, *score), word.clone()), id))
+                .collect();
+            *index = adjusted;
+            for (&(score, _), id) in index.iter() {
+                self.scores.insert(*id, score);
+            }
         }
     }
 
-    fn lookup(&self, pattern: &Pattern) -> Vec<Word> {
-        let mut pairs = self.words_by_size.get(&pattern.size()).unwrap().into_iter()
-            .filter(|&(w, _)| pattern.matches(w))
-            .map(|(w, r)| (w.clone(), r.clone()))
-            .collect::<Vec<(Word, i32)>>();
-        pairs.sort_by_key(|&(_, rank)| -rank);
-        pairs.into_iter().map(|pair| pair.0).collect()
+    // the words in both dictionaries, with the scores they have in this one
+    pub fn intersect(&self, other: &RankedDictionary) -> RankedDictionary {
+        self.filtered(|word| other.contains(word))
+    }
+
+    // the words in this dictionary that aren't in the other one
+    pub fn difference(&self, other: &RankedDictionary) -> RankedDictionary {
+        self.filtered(|word| !other.contains(word))
+    }
+
+    // a new dictionary with just the words that pass the filter
+    fn filtered<F: Fn(&Word) -> bool>(&self, keep: F) -> RankedDictionary {
+        let mut dict = RankedDictionary::new();
+        dict.default_score = self.default_score;
+        for ((score, word), id) in self.by_score.values().flatten() {
+            if keep(word) {
+                let new_id = dict.insert_scored(word, *score);
+                if let Some(form) = self.display_forms.get(id) {
+                    dict.display_forms.insert(new_id, form.clone());
+                }
+            }
+        }
+        dict
+    }
+
+    // the total number of words in the dictionary
+    pub fn len(&self) -> usize {
+        self.scores.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // the number of words of each length, sorted by length
+    // lengths without any words are left out
+    pub fn length_histogram(&self) -> Vec<(usize, usize)> {
+        let mut histogram = self.by_score.iter()
+            .map(|(&size, index)| (size, index.len()))
+            .filter(|&(_, count)| count > 0)
+            .collect::<Vec<_>>();
+        histogram.sort();
+        histogram
+    }
+
+    // the mean score of all the words in the dictionary, 0 if it's empty
+    pub fn mean_rank(&self) -> f32 {
+        if self.is_empty() {
+            return 0f32;
+        }
+        let total: i64 = self.scores.values().map(|&score| score as i64).sum();
+        (total as f32) / (self.len() as f32)
+    }
+
+    // summarizes the dictionary as a json object, e.g.
+    // {"words": 3, "lengths": {"3": 2, "5": 1}, "min_score": 10, "max_score": 50, "mean_score": 30}
+    pub fn stats_json(&self) -> String {
+        format!("{{\"words\": {}, \"lengths\": {}, \"min_score\": {}, \"max_score\": {}, \"mean_score\": {}}}",
+                self.len(),
+                histogram_json(&self.length_histogram()),
+                self.min_rank(),
+                self.max_rank(),
+                self.mean_rank())
     }
 }
 
-impl RankedDict for RankedDictionary {
+impl UnrankedDict for RankedDictionary {
     fn from_file<P: AsRef<Path>>(path: P) -> io::Result<RankedDictionary> {
         // read the file
         let mut entire = String::new();
@@ -155,71 +570,837 @@ impl RankedDict for RankedDictionary {
 
         // split the file into words and add them to the dict
         let mut dict = RankedDictionary::new();
+        let default_score = dict.default_score;
         for line in entire.split('\n') {
-            let parts = line.splitn(2, ';').collect::<Vec<_>>();
-            // println!("{:?}", parts);
-            if parts.len() != 2 {
-                continue;
+            let word = Word::from(line);
+            let id = dict.insert_scored(&word, default_score);
+            if let Some(form) = display_form_of(line, &word) {
+                dict.display_forms.insert(id, form);
             }
-            let score = parts[1].trim().parse::<i32>().unwrap();
-            let word = Word::from(parts[0]);
-            dict.add(&word);
-            dict.set_score(&word, score);
         }
         Ok(dict)
     }
 
-    fn get_score(&self, word: &Word) -> Option<i32> {
-        match self.words_by_size.get(&word.size()) {
-            Some(map) => map.get(word).cloned(),
-            None => None,
+    fn add(&mut self, word: &Word) {
+        let score = self.default_score;
+        self.insert_scored(word, score);
+    }
+
+    fn remove(&mut self, word: &Word) {
+        let id = match self.pool.id(word) {
+            Some(id) => id,
+            None => return,
+        };
+        if let Some(score) = self.scores.remove(&id) {
+            if let Some(index) = self.by_score.get_mut(&word.size()) {
+                index.remove(&(score, self.pool.get_shared(id)));
+            }
         }
+        self.display_forms.remove(&id);
+    }
+}
+
+impl DictLookup for RankedDictionary {
+    fn word(&self, id: WordId) -> &Word {
+        self.pool.get(id)
+    }
+
+    fn word_id(&self, word: &Word) -> Option<WordId> {
+        self.pool.id(word).filter(|id| self.scores.contains_key(id))
+    }
+
+    fn lookup_ids(&self, pattern: &Pattern) -> impl Iterator<Item = WordId> {
+        let pool = &self.pool;
+        self.by_score.get(&pattern.size())
+            .into_iter()
+            .flat_map(|index| index.values())
+            .cloned()
+            .filter(move |&id| pool.matches(id, pattern))
+    }
+
+    fn id_matches(&self, id: WordId, pattern: &Pattern) -> bool {
+        self.pool.matches(id, pattern)
+    }
+
+    // unlike lookup_iter, the words are sorted from highest to lowest score
+    fn lookup(&self, pattern: &Pattern) -> Vec<Word> {
+        self.lookup_range(pattern, None, None)
+    }
+}
+
+impl RankedDict for RankedDictionary {
+    // lines look like word;score, see from_file_with_delim
+    fn from_file<P: AsRef<Path>>(path: P) -> io::Result<RankedDictionary> {
+        RankedDictionary::from_file_with_delim(path, ';')
     }
 
     fn set_score(&mut self, word: &Word, rank: i32) -> bool {
         if !self.contains(word) {
             return false;
         }
-        self.words_by_size.entry(word.size())
-            .or_insert(HashMap::new())
-            .insert(word.clone(), rank);
-        // if let Some(mut map) = self.words_by_size.get_mut(&word.size()) {
-        //     map.insert(word.clone(), rank);
-        // }
+        self.insert_scored(word, rank);
         true
     }
+}
 
-    fn lookup_range(&self, pattern: &Pattern, lower: Option<i32>, upper: Option<i32>) -> Vec<Word> {
-        self.words_by_size.get(&pattern.size()).unwrap().iter()
-            .filter(|&(word, _)| pattern.matches(word))
-            .filter(|&(_, &rank)| {
-                if let Some(bound) = lower {
-                    bound <= rank 
-                } else {
-                    true
-                }
-            })
-            .filter(|&(_, &rank)| {
-                if let Some(bound) = upper {
-                    rank <= bound
-                } else {
-                    true
-                }
-            })
-            .map(|(w, _)| w)
-            .cloned()
+impl RankedDictLookup for RankedDictionary {
+    fn score(&self, id: WordId) -> Option<i32> {
+        self.scores.get(&id).cloned()
+    }
+
+    // the words are sorted from highest to lowest score
+    fn lookup_range_ids(&self, pattern: &Pattern, lower: Option<i32>, upper: Option<i32>) -> Vec<WordId> {
+        if let (Some(lower), Some(upper)) = (lower, upper) {
+            if lower > upper {
+                return vec![];
+            }
+        }
+        let index = match self.by_score.get(&pattern.size()) {
+            Some(index) => index,
+            None => return vec![],
+        };
+        // the empty word sorts before every other word, so (score, empty word)
+        // comes before every word with that score
+        let empty = Arc::new(Word::default());
+        let start = match lower {
+            Some(bound) => Bound::Included((bound, empty.clone())),
+            None => Bound::Unbounded,
+        };
+        let end = match upper.and_then(|bound| bound.checked_add(1)) {
+            Some(bound) => Bound::Excluded((bound, empty)),
+            None => Bound::Unbounded,
+        };
+        index.range((start, end))
+            .rev()
+            .map(|(_, &id)| id)
+            .filter(|&id| self.pool.matches(id, pattern))
             .collect()
     }
 
+    // the highest score in the dictionary, or the default score if it's empty
     fn max_rank(&self) -> i32 {
-        unimplemented!()
+        self.scores.values().cloned().max().unwrap_or(self.default_score)
     }
 
+    // the lowest score in the dictionary, or the default score if it's empty
     fn min_rank(&self) -> i32 {
-        unimplemented!()
+        self.scores.values().cloned().min().unwrap_or(self.default_score)
     }
 }
 
+// TrieDictionary
+// an unranked dictionary that stores words in a trie for each length
+// looking up a pattern only walks the branches that match its fixed letters,
+// instead of testing every word of the right length
+
+#[derive(Clone, Debug, Default)]
+pub struct TrieDictionary {
+    // every word that's been in the dictionary
+    pool: WordPool,
+    roots: HashMap<usize, TrieNode>,
+}
+
+#[derive(Clone, Debug, Default)]
+struct TrieNode {
+    // sorted by letter
+    children: Vec<(Letter, TrieNode)>,
+    // set on the nodes at the end of a word
+    word: Option<WordId>,
+}
+
+impl TrieDictionary {
+    pub fn new() -> TrieDictionary {
+        TrieDictionary::default()
+    }
+
+    // build a dictionary from words that are already in memory
+    pub fn from_words<I: IntoIterator<Item = Word>>(words: I) -> TrieDictionary {
+        let mut dict = TrieDictionary::new();
+        for word in words {
+            dict.add(&word);
+        }
+        dict
+    }
+}
+
+impl TrieNode {
+    fn child(&self, letter: Letter) -> Option<&TrieNode> {
+        self.children.binary_search_by_key(&letter, |&(l, _)| l)
+            .ok()
+            .map(|i| &self.children[i].1)
+    }
+
+    // the child for the letter, adding it if it isn't there yet
+    fn child_or_insert(&mut self, letter: Letter) -> &mut TrieNode {
+        let i = match self.children.binary_search_by_key(&letter, |&(l, _)| l) {
+            Ok(i) => i,
+            Err(i) => {
+                self.children.insert(i, (letter, TrieNode::default()));
+                i
+            }
+        };
+        &mut self.children[i].1
+    }
+
+    // removes the word below this node, the letters being what's left of it
+    // returns true if this node is now empty and can be removed too
+    fn remove(&mut self, letters: &[Letter]) -> bool {
+        match letters.split_first() {
+            None => {
+                self.word = None;
+            }
+            Some((letter, rest)) => {
+                if let Ok(i) = self.children.binary_search_by_key(letter, |&(l, _)| l) {
+                    if self.children[i].1.remove(rest) {
+                        self.children.remove(i);
+                    }
+                }
+            }
+        }
+        self.word.is_none() && self.children.is_empty()
+    }
+}
+
+impl UnrankedDict for TrieDictionary {
+    fn from_file<P: AsRef<Path>>(path: P) -> io::Result<TrieDictionary> {
+        // read the file
+        let mut entire = String::new();
+        File::open(path)?.read_to_string(&mut entire)?;
+
+        // split the file into words and add them to the dict
+        Ok(TrieDictionary::from_words(entire.split('\n').map(Word::from)))
+    }
+
+    fn add(&mut self, word: &Word) {
+        let id = self.pool.intern(word);
+        let mut node = self.roots.entry(word.size()).or_default();
+        for letter in &word.letters {
+            node = node.child_or_insert(*letter);
+        }
+        node.word = Some(id);
+    }
+
+    fn remove(&mut self, word: &Word) {
+        let empty = match self.roots.get_mut(&word.size()) {
+            Some(root) => root.remove(&word.letters),
+            None => false,
+        };
+        if empty {
+            self.roots.remove(&word.size());
+        }
+    }
+}
+
+impl DictLookup for TrieDictionary {
+    fn word(&self, id: WordId) -> &Word {
+        self.pool.get(id)
+    }
+
+    fn word_id(&self, word: &Word) -> Option<WordId> {
+        let mut node = self.roots.get(&word.size())?;
+        for letter in &word.letters {
+            node = node.child(*letter)?;
+        }
+        node.word
+    }
+
+    fn lookup_ids(&self, pattern: &Pattern) -> impl Iterator<Item = WordId> {
+        TrieMatches {
+            masks: pattern.masks(),
+            stack: self.roots.get(&pattern.size()).map(|root| (root, 0)).into_iter().collect(),
+        }
+    }
+
+    fn id_matches(&self, id: WordId, pattern: &Pattern) -> bool {
+        self.pool.matches(id, pattern)
+    }
+}
+
+// TrieMatches
+// walks a trie depth first, only going down the branches that match the pattern
+
+struct TrieMatches<'a, 'p> {
+    masks: &'p [PatternClass],
+    // the nodes still to visit, and how many letters deep they are
+    stack: Vec<(&'a TrieNode, usize)>,
+}
+
+impl<'a, 'p> Iterator for TrieMatches<'a, 'p> {
+    type Item = WordId;
+
+    fn next(&mut self) -> Option<WordId> {
+        while let Some((node, depth)) = self.stack.pop() {
+            if depth == self.masks.len() {
+                if node.word.is_some() {
+                    return node.word;
+                }
+                continue;
+            }
+            match self.masks[depth] {
+                PatternClass::Exact(letter) => {
+                    if let Some(child) = node.child(letter) {
+                        self.stack.push((child, depth + 1));
+                    }
+                }
+                ref class => {
+                    self.stack.extend(node.children.iter()
+                        .filter(|&&(letter, _)| class.matches(letter))
+                        .map(|(_, child)| (child, depth + 1)));
+                }
+            }
+        }
+        None
+    }
+}
+
+// shared and borrowed dictionaries
+// a dictionary behind an Rc, an Arc or a reference can be looked up in too,
+// so one loaded dictionary can back any number of solvers,
+// e.g. GridSolver::new(grid, Rc::clone(&dict)) or GridSolver::new(grid, &dict)
+
+macro_rules! forwarded_lookup_impls {
+    ($outer:ty) => {
+        impl<T: DictLookup> DictLookup for $outer {
+            fn word(&self, id: WordId) -> &Word {
+                (**self).word(id)
+            }
+
+            fn word_id(&self, word: &Word) -> Option<WordId> {
+                (**self).word_id(word)
+            }
+
+            fn lookup_ids(&self, pattern: &Pattern) -> impl Iterator<Item = WordId> {
+                (**self).lookup_ids(pattern)
+            }
+
+            fn id_matches(&self, id: WordId, pattern: &Pattern) -> bool {
+                (**self).id_matches(id, pattern)
+            }
+
+            fn lookup(&self, pattern: &Pattern) -> Vec<Word> {
+                (**self).lookup(pattern)
+            }
+
+            fn count_matches(&self, pattern: &Pattern) -> usize {
+                (**self).count_matches(pattern)
+            }
+        }
+
+        impl<T: RankedDictLookup> RankedDictLookup for $outer {
+            fn score(&self, id: WordId) -> Option<i32> {
+                (**self).score(id)
+            }
+
+            fn lookup_range_ids(&self, pattern: &Pattern, lower: Option<i32>, upper: Option<i32>) -> Vec<WordId> {
+                (**self).lookup_range_ids(pattern, lower, upper)
+            }
+
+            fn max_rank(&self) -> i32 {
+                (**self).max_rank()
+            }
+
+            fn min_rank(&self) -> i32 {
+                (**self).min_rank()
+            }
+        }
+    };
+}
+
+forwarded_lookup_impls!(Rc<T>);
+forwarded_lookup_impls!(Arc<T>);
+forwarded_lookup_impls!(&T);
+
+// a shared dictionary can be loaded and changed as well
+// adding or removing words copies the dictionary first if it's shared,
+// so the other solvers don't see the change
+// a borrowed one can't be, so it's only good for lookups
+
+macro_rules! shared_dict_impls {
+    ($shared:ident) => {
+        impl<T: UnrankedDict + Clone> UnrankedDict for $shared<T> {
+            fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+                <T as UnrankedDict>::from_file(path).map($shared::new)
+            }
+
+            fn add(&mut self, word: &Word) {
+                $shared::make_mut(self).add(word);
+            }
+
+            fn remove(&mut self, word: &Word) {
+                $shared::make_mut(self).remove(word);
+            }
+        }
+
+        impl<T: RankedDict + Clone> RankedDict for $shared<T> {
+            fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+                <T as RankedDict>::from_file(path).map($shared::new)
+            }
+
+            fn set_score(&mut self, word: &Word, rank: i32) -> bool {
+                $shared::make_mut(self).set_score(word, rank)
+            }
+        }
+    };
+}
+
+shared_dict_impls!(Rc);
+shared_dict_impls!(Arc);
+
+// how a line of a word list should be shown if it isn't just letters, e.g. O'CLOCK or ICE CREAM
+fn display_form_of(line: &str, word: &Word) -> Option<String> {
+    let line = line.trim();
+    if word.size() > 0 && line.chars().any(|c| !c.is_alphabetic()) {
+        Some(line.to_uppercase())
+    } else {
+        None
+    }
+}
+
+// remove the word from the set under the key, dropping the set if that empties it
+fn remove_from<K: Eq + Hash>(index: &mut HashMap<K, HashSet<WordId>>, key: K, id: WordId) {
+    if let Some(set) = index.get_mut(&key) {
+        set.remove(&id);
+        if set.is_empty() {
+            index.remove(&key);
+        }
+    }
+}
+
+// the letters in alphabetical order, which all anagrams of them share
+fn sorted_letters(letters: &[Letter]) -> Vec<Letter> {
+    let mut sorted = letters.to_vec();
+    sorted.sort();
+    sorted
+}
+
+// formats a length histogram as a json object mapping length to word count
+fn histogram_json(histogram: &[(usize, usize)]) -> String {
+    let fields = histogram.iter()
+        .map(|&(size, count)| format!("\"{}\": {}", size, count))
+        .collect::<Vec<_>>();
+    format!("{{{}}}", fields.join(", "))
+}
+
 #[cfg(test)]
 mod test {
+    use super::*;
+    use serde_json::{self, Value};
+
+    #[test]
+    fn lookup_iter_matches_lookup() {
+        let mut dict = Dictionary::new();
+        for word in &["CAT", "COT", "DOG", "HORSE"] {
+            dict.add(&Word::from(*word));
+        }
+        for pattern in &["C.T", "...", "..G", "Z..", "....."] {
+            let pattern = Pattern::from(*pattern);
+            let mut iterated = dict.lookup_iter(&pattern).cloned().collect::<Vec<_>>();
+            let mut looked_up = dict.lookup(&pattern);
+            iterated.sort();
+            looked_up.sort();
+            assert_eq!(iterated, looked_up);
+        }
+        assert_eq!(dict.lookup_iter(&Pattern::from("C.T")).count(), 2);
+    }
+
+    #[test]
+    fn letter_index_matches_a_scan() {
+        let mut dict = Dictionary::from_words(
+            ["CAT", "COT", "CUT", "DOG", "TAX", "AXIS", "ZEST", "HORSE"].iter().map(|w| Word::from(*w)));
+        dict.remove(&Word::from("CUT"));
+        dict.remove(&Word::from("CUT"));
+        for pattern in &["C.T", "..T", ".A.", "...", ".X..", "Z.ST", "Q..", "C.X", "....."] {
+            let pattern = Pattern::from(*pattern);
+            let mut scanned = dict.words_by_size[&pattern.size()].iter()
+                .map(|&id| dict.pool.get(id).clone())
+                .filter(|word| pattern.matches(word))
+                .collect::<Vec<_>>();
+            let mut indexed = dict.lookup(&pattern);
+            scanned.sort();
+            indexed.sort();
+            assert_eq!(indexed, scanned);
+        }
+        assert_eq!(dict.lookup(&Pattern::from("C.T")).len(), 2);
+    }
+
+    #[test]
+    fn lookups_understand_pattern_classes() {
+        let words = ["CAT", "COT", "CUT", "BAT", "BOA"].iter().map(|w| Word::from(*w)).collect::<Vec<_>>();
+        let dict = Dictionary::from_words(words.clone());
+        let trie = TrieDictionary::from_words(words.clone());
+        let ranked = RankedDictionary::from_words(words);
+        for &(pattern, expected) in &[("C[AO]T", &["CAT", "COT"][..]),
+                                      ("[^C][AO].", &["BAT", "BOA"]),
+                                      ("[BC].[^T]", &["BOA"]),
+                                      (".[^AO]T", &["CUT"])] {
+            let pattern = Pattern::from(pattern);
+            let expected = expected.iter().map(|w| Word::from(*w)).collect::<Vec<_>>();
+            for mut found in [dict.lookup(&pattern), trie.lookup(&pattern), ranked.lookup(&pattern)] {
+                found.sort();
+                assert_eq!(found, expected, "{}", pattern);
+            }
+        }
+    }
+
+    #[test]
+    fn from_words_builds_a_dictionary() {
+        let dict = Dictionary::from_words(vec![Word::from("CAT"), Word::from("COT"), Word::from("DOG")]);
+        let mut found = dict.lookup(&Pattern::from("C.T"));
+        found.sort();
+        assert_eq!(found, vec![Word::from("CAT"), Word::from("COT")]);
+
+        let ranked = RankedDictionary::from_scored_words(vec![(Word::from("CAT"), 10), (Word::from("COT"), 50)]);
+        assert_eq!(ranked.lookup(&Pattern::from("C.T")), vec![Word::from("COT"), Word::from("CAT")]);
+        assert_eq!(ranked.get_score(&Word::from("CAT")), Some(10));
+        let unscored = RankedDictionary::from_words(vec![Word::from("CAT")]);
+        assert_eq!(unscored.get_score(&Word::from("CAT")), Some(0));
+    }
+
+    #[test]
+    fn lookups_of_missing_lengths_find_nothing() {
+        let words = vec![Word::from("CAT"), Word::from("DOGS")];
+        let long = Pattern::from("....................");
+        let long_fixed = Pattern::from("A...................");
+        let dict = Dictionary::from_words(words.clone());
+        assert!(dict.lookup(&long).is_empty());
+        assert!(dict.lookup(&long_fixed).is_empty());
+        let trie = TrieDictionary::from_words(words.clone());
+        assert!(trie.lookup(&long).is_empty());
+        let ranked = RankedDictionary::from_words(words);
+        assert!(ranked.lookup(&long).is_empty());
+        assert!(ranked.lookup_range(&long, None, None).is_empty());
+        assert!(ranked.lookup_range(&long_fixed, Some(0), Some(100)).is_empty());
+    }
+
+    #[test]
+    fn anagrams_use_every_letter_once() {
+        let mut dict = Dictionary::from_words(["LISTEN", "SILENT", "TINSEL", "LISTENS", "LISSEN", "TEN", "NET"]
+            .iter()
+            .map(|w| Word::from(*w)));
+        let letters = Word::from("LISTEN").letters;
+        assert_eq!(dict.anagrams(&letters), vec![Word::from("LISTEN"), Word::from("SILENT"), Word::from("TINSEL")]);
+        // every given letter has to be used, including repeats
+        assert_eq!(dict.anagrams(&Word::from("SLISTEN").letters), vec![Word::from("LISTENS")]);
+        assert_eq!(dict.anagrams(&Word::from("ENT").letters), vec![Word::from("NET"), Word::from("TEN")]);
+        assert!(dict.anagrams(&Word::from("LISTNE").letters[..5]).is_empty());
+
+        dict.remove(&Word::from("SILENT"));
+        assert_eq!(dict.anagrams(&letters), vec![Word::from("LISTEN"), Word::from("TINSEL")]);
+    }
+
+    #[test]
+    fn containing_finds_infixes_anywhere() {
+        let dict = Dictionary::from_words(["CATNIP", "SCATTER", "BOBCAT", "CAT", "ACT", "CART", "DOG"]
+            .iter()
+            .map(|w| Word::from(*w)));
+        let words = |infix: &str| dict.containing(&Word::from(infix)).into_iter().map(|w| w.to_string()).collect::<Vec<_>>();
+        assert_eq!(words("CAT"), vec!["BOBCAT", "CAT", "CATNIP", "SCATTER"]);
+        // start, middle and end
+        assert_eq!(words("CATN"), vec!["CATNIP"]);
+        assert_eq!(words("ATT"), vec!["SCATTER"]);
+        assert_eq!(words("BCAT"), vec!["BOBCAT"]);
+        // the letters have to be next to each other
+        assert!(words("CTA").is_empty());
+        assert!(words("CATS").is_empty());
+        assert_eq!(words("").len(), 7);
+    }
+
+    #[test]
+    fn loaders_keep_punctuated_display_forms() {
+        let path = ::std::env::temp_dir().join("gridsolver_display_forms.txt");
+        File::create(&path).unwrap().write_all(b"O'CLOCK\nt-shirt\nCAT\n").unwrap();
+        let dict = <Dictionary as UnrankedDict>::from_file(&path).unwrap();
+        let ranked = <RankedDictionary as UnrankedDict>::from_file(&path).unwrap();
+        for &(word, form) in &[("OCLOCK", "O'CLOCK"), ("TSHIRT", "T-SHIRT"), ("CAT", "CAT")] {
+            let word = Word::from(word);
+            assert!(dict.contains(&word) && ranked.contains(&word));
+            assert_eq!(dict.display_form(&word), form);
+            assert_eq!(ranked.display_form(&word), form);
+        }
+
+        File::create(&path).unwrap().write_all(b"O'CLOCK;50\nT-SHIRT;40\n").unwrap();
+        let mut scored = <RankedDictionary as RankedDict>::from_file(&path).unwrap();
+        assert_eq!(scored.get_score(&Word::from("OCLOCK")), Some(50));
+        assert_eq!(scored.display_form(&Word::from("TSHIRT")), "T-SHIRT");
+        scored.remove(&Word::from("TSHIRT"));
+        scored.add(&Word::from("TSHIRT"));
+        assert_eq!(scored.display_form(&Word::from("TSHIRT")), "TSHIRT");
+    }
+
+    #[test]
+    fn ranked_loading_survives_bad_lines() {
+        let path = ::std::env::temp_dir().join("gridsolver_bad_lines.txt");
+        File::create(&path).unwrap().write_all(b"CAT;50\n\nDOG\nEMU;\nFOX;lots\nGNU;1;2\nHEN; 30\r\n").unwrap();
+        let dict = <RankedDictionary as RankedDict>::from_file(&path).unwrap();
+        assert_eq!(dict.get_score(&Word::from("CAT")), Some(50));
+        assert_eq!(dict.get_score(&Word::from("HEN")), Some(30));
+        // a missing or garbled score falls back to the default
+        assert_eq!(dict.get_score(&Word::from("EMU")), Some(0));
+        assert_eq!(dict.get_score(&Word::from("FOX")), Some(0));
+        // lines with too many parts are left out
+        assert!(!dict.contains(&Word::from("GNU")));
+        assert_eq!(dict.len(), 5);
+
+        File::create(&path).unwrap().write_all(b"CAT,50\nDOG\t60\n").unwrap();
+        let dict = RankedDictionary::from_file_with_delim(&path, ',').unwrap();
+        assert_eq!(dict.get_score(&Word::from("CAT")), Some(50));
+        let dict = RankedDictionary::from_file_with_delim(&path, '\t').unwrap();
+        assert_eq!(dict.get_score(&Word::from("DOG")), Some(60));
+    }
+
+    #[test]
+    fn ranked_loading_gives_bare_words_the_default_score() {
+        let path = ::std::env::temp_dir().join("gridsolver_bare_words.txt");
+        File::create(&path).unwrap().write_all(b"CAT;50\nDOG\nEMU;70\nGNU\n").unwrap();
+        let dict = <RankedDictionary as RankedDict>::from_file(&path).unwrap();
+        assert_eq!(dict.len(), 4);
+        assert_eq!(dict.get_score(&Word::from("CAT")), Some(50));
+        assert_eq!(dict.get_score(&Word::from("EMU")), Some(70));
+        assert_eq!(dict.get_score(&Word::from("DOG")), Some(dict.default_score));
+        assert_eq!(dict.get_score(&Word::from("GNU")), Some(dict.default_score));
+    }
+
+    #[test]
+    fn adjust_scores_remaps_matching_words() {
+        let mut dict = RankedDictionary::from_scored_words(vec![
+            (Word::from("CAT"), 50), (Word::from("DOG"), 60), (Word::from("HORSE"), 40),
+        ]);
+        dict.adjust_scores(|word, score| if word.size() == 3 { score - 20 } else { score });
+        assert_eq!(dict.get_score(&Word::from("CAT")), Some(30));
+        assert_eq!(dict.get_score(&Word::from("DOG")), Some(40));
+        assert_eq!(dict.get_score(&Word::from("HORSE")), Some(40));
+
+        // the score index follows the new scores
+        let pattern = Pattern::from("...");
+        assert_eq!(dict.lookup_range(&pattern, Some(35), None), vec![Word::from("DOG")]);
+        assert_eq!(dict.lookup(&pattern), vec![Word::from("DOG"), Word::from("CAT")]);
+        assert_eq!(dict.max_rank(), 40);
+    }
+
+    #[test]
+    fn nearest_finds_the_closest_words() {
+        let dict = Dictionary::from_words(["CAT", "COAT", "DOG", "HORSE", "CART", "BAT"].iter().map(|w| Word::from(*w)));
+        let nearest = dict.nearest(&Word::from("CAST"), 3);
+        // CART and CAT are one edit away, then BAT and COAT are two
+        assert_eq!(nearest, vec![Word::from("CART"), Word::from("CAT"), Word::from("BAT")]);
+        assert_eq!(dict.nearest(&Word::from("CAST"), 10).len(), 6);
+        assert!(dict.nearest(&Word::from("CAST"), 0).is_empty());
+    }
+
+    #[test]
+    fn trie_dictionary_adds_and_removes() {
+        let mut dict = TrieDictionary::from_words(["CAT", "CAR", "DOG"].iter().map(|w| Word::from(*w)));
+        assert!(dict.contains(&Word::from("CAR")));
+        assert!(!dict.contains(&Word::from("CA")));
+        dict.remove(&Word::from("CAR"));
+        dict.remove(&Word::from("COW"));
+        assert!(!dict.contains(&Word::from("CAR")));
+        assert_eq!(dict.lookup(&Pattern::from("CA.")), vec![Word::from("CAT")]);
+        dict.remove(&Word::from("CAT"));
+        dict.remove(&Word::from("DOG"));
+        assert!(dict.roots.is_empty());
+    }
+
+    #[test]
+    fn trie_dictionary_matches_dictionary() {
+        // see benches/lookups.rs for how the two compare on a full word list
+        let words = ["CAT", "COT", "CUT", "ACT", "SCAT", "SEAT", "SLATE", "STATE", "QUOTA", "TOTE", "", "AARDVARK"]
+            .iter()
+            .map(|word| Word::from(*word))
+            .collect::<Vec<_>>();
+        let dict = Dictionary::from_words(words.clone());
+        let trie = TrieDictionary::from_words(words);
+
+        let patterns = ["C.T", "....", "Q....", "...E.", "S.....", ".T.T.", "A..", "XZ.", ""];
+        for pattern in &patterns {
+            let pattern = Pattern::from(*pattern);
+            let mut expected = dict.lookup(&pattern);
+            let mut found = trie.lookup(&pattern);
+            expected.sort();
+            found.sort();
+            assert_eq!(found, expected);
+        }
+    }
+
+    #[test]
+    fn removing_words_drops_empty_sets() {
+        let mut dict = Dictionary::from_words(["CAT", "ACT", "COT"].iter().map(|w| Word::from(*w)));
+        let c = Word::from("C").letters[0];
+        dict.remove(&Word::from("CAT"));
+        // COT still starts with C and ACT is still an anagram of CAT
+        assert_eq!(dict.words_by_letter[&(3, 0, c)].len(), 1);
+        assert_eq!(dict.words_by_anagram[&sorted_letters(&Word::from("CAT").letters)].len(), 1);
+        // but no word has A second any more
+        assert!(!dict.words_by_letter.contains_key(&(3, 1, Word::from("A").letters[0])));
+
+        dict.remove(&Word::from("ACT"));
+        dict.remove(&Word::from("COT"));
+        assert!(dict.words_by_size.is_empty());
+        assert!(dict.words_by_letter.is_empty());
+        assert!(dict.words_by_anagram.is_empty());
+        assert!(dict.is_empty());
+    }
+
+    #[test]
+    fn packed_matching_agrees_with_pattern_matching() {
+        // see benches/lookups.rs for how the two compare on a full word list
+        let words = ["CAT", "COT", "CUT", "CATS", "SEAT", "STAR", "ABCDEFGHIJKLMNO"].iter().map(|w| Word::from(*w)).collect::<Vec<_>>();
+        let dict = Dictionary::from_words(words.clone());
+        let ranked = RankedDictionary::from_words(words.clone());
+        let trie = TrieDictionary::from_words(words.clone());
+        let patterns = ["C.T", "....", "S...", "[AEIOU]...", ".[^O].", "...T", "ABCDEFGHIJKLMN.", "..............."];
+        for pattern in &patterns {
+            let pattern = Pattern::from(*pattern);
+            for word in &words {
+                let expected = pattern.matches(word);
+                assert_eq!(dict.id_matches(dict.word_id(word).unwrap(), &pattern), expected, "{}", word);
+                assert_eq!(ranked.id_matches(ranked.word_id(word).unwrap(), &pattern), expected, "{}", word);
+                assert_eq!(trie.id_matches(trie.word_id(word).unwrap(), &pattern), expected, "{}", word);
+            }
+        }
+    }
+
+    #[test]
+    fn count_matches_agrees_with_lookup() {
+        fn check<D: DictLookup>(dict: &D) {
+            for pattern in &["...", "C..", ".A.", "C.T", "[CB]..", "[^C]A.", "Q..", "....", "", "HORSE", "H...."] {
+                let pattern = Pattern::from(*pattern);
+                assert_eq!(dict.count_matches(&pattern), dict.lookup(&pattern).len(), "{}", pattern);
+            }
+        }
+        let words = ["CAT", "COT", "CUT", "BAT", "BOA", "HORSE", "HOUSE"].iter().map(|w| Word::from(*w)).collect::<Vec<_>>();
+        let mut dict = Dictionary::from_words(words.clone());
+        dict.remove(&Word::from("CUT"));
+        check(&dict);
+        check(&&dict);
+        check(&Rc::new(dict));
+        check(&TrieDictionary::from_words(words.clone()));
+        check(&RankedDictionary::from_scored_words(words.into_iter().map(|word| (word, 50))));
+    }
+
+    #[test]
+    fn removed_words_keep_their_ids() {
+        fn check<D: UnrankedDict>(mut dict: D) {
+            let (cat, cot) = (Word::from("CAT"), Word::from("COT"));
+            let id = dict.word_id(&cat).unwrap();
+            assert_eq!(dict.word(id), &cat);
+            let mut found = dict.lookup_ids(&Pattern::from("C.T")).map(|id| dict.word(id).clone()).collect::<Vec<_>>();
+            found.sort();
+            assert_eq!(found, vec![cat.clone(), cot.clone()]);
+
+            dict.remove(&cat);
+            assert_eq!(dict.word_id(&cat), None);
+            assert_eq!(dict.word(id), &cat);
+            assert_eq!(dict.lookup_ids(&Pattern::from("C.T")).collect::<Vec<_>>(), vec![dict.word_id(&cot).unwrap()]);
+            dict.add(&cat);
+            assert_eq!(dict.word_id(&cat), Some(id));
+        }
+        let words = ["CAT", "COT", "DOG"].iter().map(|w| Word::from(*w)).collect::<Vec<_>>();
+        check(Dictionary::from_words(words.clone()));
+        check(TrieDictionary::from_words(words.clone()));
+        check(RankedDictionary::from_words(words));
+    }
+
+    #[test]
+    fn stats_json_round_trips() {
+        let mut dict = Dictionary::new();
+        for word in &["CAT", "DOG", "HORSE"] {
+            dict.add(&Word::from(*word));
+        }
+        let stats: Value = serde_json::from_str(&dict.stats_json()).unwrap();
+        assert_eq!(stats["words"], 3);
+        assert_eq!(stats["lengths"]["3"], 2);
+        assert_eq!(stats["lengths"]["5"], 1);
+        assert_eq!(stats["lengths"].as_object().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn stats_count_lengths_and_letters() {
+        let words = ["THE", "QUICK", "BROWN", "FOX", "JUMPS", "OVER", "LAZY", "SLEEPING", "DOGS", "HERE"];
+        let dict = Dictionary::from_words(words.iter().map(|word| Word::from(*word)));
+        let stats = dict.stats();
+        assert_eq!(stats.words, 10);
+        assert_eq!(stats.lengths, vec![(3, 2), (4, 4), (5, 3), (8, 1)]);
+        assert_eq!(stats.letter_counts[0], (Word::from("E").letters[0], 6));
+        let total: usize = stats.letter_counts.iter().map(|&(_, count)| count).sum();
+        assert_eq!(total, words.iter().map(|word| word.len()).sum::<usize>());
+    }
+
+    #[test]
+    fn intersect_and_difference_split_the_shared_words() {
+        let words = |list: &[&str]| list.iter().map(|word| Word::from(*word)).collect::<Vec<_>>();
+        let sorted = |dict: &Dictionary| {
+            let mut found = dict.words().cloned().collect::<Vec<_>>();
+            found.sort();
+            found
+        };
+        let safe = Dictionary::from_words(words(&["CAT", "DOG", "HORSE"]));
+        let raw = Dictionary::from_words(words(&["CAT", "COW", "HORSE", "ZEBRA"]));
+        assert_eq!(sorted(&raw.intersect(&safe)), words(&["CAT", "HORSE"]));
+        assert_eq!(sorted(&raw.difference(&safe)), words(&["COW", "ZEBRA"]));
+        assert_eq!(raw.difference(&safe).len() + raw.intersect(&safe).len(), raw.len());
+        assert!(raw.difference(&raw).is_empty());
+
+        let scored = RankedDictionary::from_scored_words(vec![(Word::from("CAT"), 50), (Word::from("COW"), 20)]);
+        let other = RankedDictionary::from_scored_words(vec![(Word::from("CAT"), 10)]);
+        let both = scored.intersect(&other);
+        assert_eq!(both.get_score(&Word::from("CAT")), Some(50));
+        assert_eq!(both.len(), 1);
+        let only = scored.difference(&other);
+        assert_eq!(only.lookup(&Pattern::from("...")), vec![Word::from("COW")]);
+    }
+
+    #[test]
+    fn lookup_range_matches_a_scan() {
+        let mut dict = RankedDictionary::new();
+        let words = ["CAT", "COT", "CUT", "DOG", "DIG", "HORSE", "HOUSE"];
+        for (i, word) in words.iter().enumerate() {
+            let word = Word::from(*word);
+            dict.add(&word);
+            dict.set_score(&word, (i as i32) * 10);
+        }
+        // changing a score and removing a word keep the index up to date
+        dict.set_score(&Word::from("DOG"), 55);
+        dict.remove(&Word::from("CUT"));
+
+        let bounds = [None, Some(0), Some(10), Some(30), Some(55), Some(100), Some(i32::MAX)];
+        for pattern in &["C.T", "...", "D..", "HO.SE", "....."] {
+            let pattern = Pattern::from(*pattern);
+            for &lower in &bounds {
+                for &upper in &bounds {
+                    let mut scanned = dict.scores.iter()
+                        .map(|(&id, score)| (dict.pool.get(id), score))
+                        .filter(|&(word, &score)| {
+                            word.size() == pattern.size()
+                                && pattern.matches(word)
+                                && lower.is_none_or(|bound| bound <= score)
+                                && upper.is_none_or(|bound| score <= bound)
+                        })
+                        .map(|(word, &score)| (score, word.clone()))
+                        .collect::<Vec<_>>();
+                    scanned.sort();
+                    scanned.reverse();
+                    let scanned = scanned.into_iter().map(|(_, word)| word).collect::<Vec<_>>();
+                    assert_eq!(dict.lookup_range(&pattern, lower, upper), scanned);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn ranked_stats_json_round_trips() {
+        let mut dict = RankedDictionary::new();
+        for &(word, score) in &[("CAT", 10), ("DOG", 20), ("HORSE", 60)] {
+            let word = Word::from(word);
+            dict.add(&word);
+            dict.set_score(&word, score);
+        }
+        let stats: Value = serde_json::from_str(&dict.stats_json()).unwrap();
+        assert_eq!(stats["words"], 3);
+        assert_eq!(stats["lengths"]["3"], 2);
+        assert_eq!(stats["lengths"]["5"], 1);
+        assert_eq!(stats["min_score"], 10);
+        assert_eq!(stats["max_score"], 60);
+        assert_eq!(stats["mean_score"], 30.0);
+    }
 }
@@ -1,21 +1,25 @@
+use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::fs::File;
+use std::hash::Hash;
 use std::io::prelude::*;
 use std::io;
 use std::iter::Iterator;
 use std::path::Path;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 use try_from::TryFrom;
 
-use rand::{thread_rng, Rng};
+use rand::{thread_rng, Rng, SeedableRng, StdRng};
 
 use basic_types::*;
-use dict::{UnrankedDict, RankedDict}; 
+use dict::{DictLookup, RankedDictLookup, UnrankedDict};
 
 // Grid
 // a grid of cells
-// an entry in the grid is a run of at least three consecutive white cells
-// in either the across or down direction
+// an entry in the grid is a run of consecutive white cells in either the
+// across or down direction, at least min_entry_len (three by default) long
 
 #[derive(Clone, Debug)]
 pub struct Grid {
@@ -28,11 +32,18 @@ pub struct Grid {
     // duh
     width: usize,
     height: usize,
+    // the shortest run of white cells that counts as an entry
+    min_entry_len: usize,
 }
 
 impl Grid {
     // construct a new empty Grid
     pub fn new(width: usize, height: usize) -> Option<Grid> {
+        Grid::with_min_entry_len(width, height, 3)
+    }
+
+    // construct a new empty Grid where entries are at least min_len long
+    pub fn with_min_entry_len(width: usize, height: usize, min_len: usize) -> Option<Grid> {
         if width == 0 || height == 0 {
             return None;
         }
@@ -40,8 +51,9 @@ impl Grid {
             cells: vec![Cell::White(None); width * height],
             entries: HashMap::new(),
             perpendicular_entries: HashMap::new(),
-            width: width,
-            height: height,
+            width,
+            height,
+            min_entry_len: min_len,
         };
         grid.rebuild();
         Some(grid)
@@ -53,15 +65,16 @@ impl Grid {
             return None;
         }
 
-        let mut cells_vec: Vec<Cell> = cells.into_iter().cloned().collect();
+        let mut cells_vec: Vec<Cell> = cells.to_vec();
         cells_vec.resize(width * height, Cell::White(None));
 
         let mut grid = Grid {
             cells: cells_vec,
             entries: HashMap::new(),
             perpendicular_entries: HashMap::new(),
-            width: width,
-            height: height,
+            width,
+            height,
+            min_entry_len: 3,
         };
         grid.rebuild();
         Some(grid)
@@ -71,46 +84,95 @@ impl Grid {
     // see the examples in the assets folder for examples
     pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Grid> {
         // read the file
-        let file = try!(File::open(path));
+        let file = File::open(path)?;
         let mut reader = io::BufReader::new(file);
         let mut entire: String = String::new();
-        try!(reader.read_to_string(&mut entire));
-
-        let dimensions: Vec<usize> = entire.chars()
-            .take_while(|c| *c != '\n')
-            .collect::<String>()
-            .split(',')
-            .map(|s| s.trim().parse().unwrap())
-            .collect();
-        let height = dimensions[0];
-        let width = dimensions[1];
+        reader.read_to_string(&mut entire)?;
 
+        let (height, width) = grid_file_header(&entire)?;
         let cells: Vec<Cell> = entire.chars()
             .skip_while(|c| *c != '\n')
             .skip(1)
-            .filter_map(|c| match c {
-                '.' => Some(Cell::White(None)),
-                '#' => Some(Cell::Black),
-                e if e.is_whitespace() => None,
-                e => Some(Cell::White(Letter::try_from(e as u8).ok())),
-            })
+            .filter(|c| !c.is_whitespace())
+            .map(cell_from_char)
             .collect();
+        if cells.len() != width * height {
+            return Err(invalid_data(format!("expected {} cells for a {}x{} grid, found {}",
+                                            width * height, height, width, cells.len())));
+        }
 
         let mut grid = Grid {
-            cells: cells,
+            cells,
             entries: HashMap::new(),
             perpendicular_entries: HashMap::new(),
-            width: width,
-            height: height,
+            width,
+            height,
+            min_entry_len: 3,
         };
         grid.rebuild();
         Ok(grid)
     }
 
+    // load a Grid from a file like from_file, where the cells may be separated
+    // by any of the given characters for readability, e.g. "C|A|T"
+    // lines made up only of separators, e.g. "-+-+-", are skipped
+    // every remaining line has to be a row of exactly width cells
+    pub fn from_file_delimited<P: AsRef<Path>>(path: P, separators: &[char]) -> io::Result<Grid> {
+        let mut entire = String::new();
+        File::open(path)?.read_to_string(&mut entire)?;
+        let (height, width) = grid_file_header(&entire)?;
+
+        let rows = entire.lines()
+            .skip(1)
+            .map(|line| {
+                line.chars()
+                    .filter(|c| !c.is_whitespace() && !separators.contains(c))
+                    .map(cell_from_char)
+                    .collect::<Vec<_>>()
+            })
+            .filter(|row| !row.is_empty())
+            .collect::<Vec<_>>();
+        if rows.len() != height {
+            return Err(invalid_data(format!("expected {} rows, found {}", height, rows.len())));
+        }
+        if let Some((i, row)) = rows.iter().enumerate().find(|&(_, row)| row.len() != width) {
+            return Err(invalid_data(format!("expected {} cells in row {}, found {}", width, i + 1, row.len())));
+        }
+
+        let cells = rows.concat();
+        Ok(Grid::from_cells(&cells, width, height).unwrap())
+    }
+
+    // construct a Grid from rows of characters, one character per cell
+    // the characters mean the same as in the grid files
+    // returns none if there are no rows or they aren't all the same length
+    pub fn from_rows(rows: &[&str]) -> Option<Grid> {
+        let width = rows.first()?.chars().count();
+        if rows.iter().any(|row| row.chars().count() != width) {
+            return None;
+        }
+        let cells: Vec<Cell> = rows.iter()
+            .flat_map(|row| row.chars())
+            .map(cell_from_char)
+            .collect();
+        Grid::from_cells(&cells, width, rows.len())
+    }
+
     pub fn width(&self) -> usize {
         self.width
     }
 
+    pub fn min_entry_len(&self) -> usize {
+        self.min_entry_len
+    }
+
+    // change the shortest run of white cells that counts as an entry
+    // and recalculate the entries
+    pub fn set_min_entry_len(&mut self, min_len: usize) {
+        self.min_entry_len = min_len;
+        self.rebuild();
+    }
+
     pub fn height(&self) -> usize {
         self.height
     }
@@ -152,6 +214,12 @@ impl Grid {
             })
     }
 
+    // the pattern of the letters already in an entry, e.g. C.T
+    // the empty cells match any letter
+    pub fn entry_pattern(&self, index: EntryIndex) -> Option<Pattern> {
+        self.get_entry(index).map(|entry| Pattern::new(&entry.letters))
+    }
+
     // set an entry to equal a given entry
     pub fn set_entry(&mut self, index: EntryIndex, entry: &Entry) {
         if self.entries.contains_key(&index) {
@@ -164,15 +232,21 @@ impl Grid {
     }
 
     // fill an entry with the given word
-    pub fn fill_entry(&mut self, index: EntryIndex, word: &Word) {
-        if self.entries.contains_key(&index) {
-            let coords: Vec<GridCoord> = self.entries[&index].clone();
-            let letters: Vec<Letter> = word.letters.clone();
-            for (coord, letter) in coords.into_iter().zip(letters.into_iter()) { 
-                let new_cell = Cell::White(Some(letter));
-                self.set_cell(coord, new_cell);
-            }
+    // the grid is left alone if there's no such entry or the word is the wrong length
+    pub fn fill_entry(&mut self, index: EntryIndex, word: &Word) -> Result<(), FillError> {
+        let coords: Vec<GridCoord> = match self.entries.get(&index) {
+            Some(coords) => coords.clone(),
+            None => return Err(FillError::NoSuchEntry),
+        };
+        if coords.len() != word.size() {
+            return Err(FillError::LengthMismatch { entry: coords.len(), word: word.size() });
+        }
+        let letters: Vec<Letter> = word.letters.clone();
+        for (coord, letter) in coords.into_iter().zip(letters) {
+            let new_cell = Cell::White(Some(letter));
+            self.set_cell(coord, new_cell);
         }
+        Ok(())
     }
 
     // erase all filled cells in the given entry
@@ -185,11 +259,35 @@ impl Grid {
         }
     }
 
+    // erase every filled cell, keeping the black cells where they are
+    // the entries don't change, so there's nothing to rebuild
+    pub fn clear_all(&mut self) {
+        for cell in &mut self.cells {
+            if let Cell::White(Some(_)) = *cell {
+                *cell = Cell::White(None);
+            }
+        }
+    }
+
     // get a list of entries perpendicular to the given one
     pub fn entries_perp_to(&self, index: EntryIndex) -> Vec<EntryIndex> {
         self.perpendicular_entries[&index].clone()
     }
 
+    // the entries passing through the given cell, at most one across and one down, in that order
+    // empty for black cells and cells outside the grid
+    pub fn entries_at(&self, coord: GridCoord) -> Vec<EntryIndex> {
+        if coord.row >= self.height || coord.col >= self.width {
+            return vec![];
+        }
+        let mut indices = self.entries.iter()
+            .filter(|&(_, coords)| coords.contains(&coord))
+            .map(|(&index, _)| index)
+            .collect::<Vec<_>>();
+        indices.sort_by_key(|index| index.dir != EntryDir::Across);
+        indices
+    }
+
     // check if an entry is filled
     pub fn is_entry_filled(&self, index: EntryIndex) -> bool {
         match self.get_entry(index) {
@@ -210,18 +308,284 @@ impl Grid {
         self.cells.iter().all(|cell| cell.is_black() || cell.is_filled())
     }
 
-    // returns all the entryindex's in the grid in arbitrary order
+    // check that every white cell belongs to at least one entry
+    // returns the coordinates of the orphaned white cells in reading order otherwise,
+    // e.g. a lone white cell or a run too short to be an entry
+    pub fn is_valid(&self) -> Result<(), Vec<GridCoord>> {
+        let in_entries: HashSet<GridCoord> = self.entries.values()
+            .flat_map(|coords| coords.iter().cloned())
+            .collect();
+        let orphans = (0..self.height)
+            .flat_map(|row| (0..self.width).map(move |col| GridCoord::new(row, col)))
+            .filter(|&coord| self.get_cell(coord).unwrap().is_white() && !in_entries.contains(&coord))
+            .collect::<Vec<_>>();
+        if orphans.is_empty() {
+            Ok(())
+        } else {
+            Err(orphans)
+        }
+    }
+
+    // check that every black cell has a black partner under the given symmetry
+    pub fn is_symmetric(&self, kind: SymmetryKind) -> bool {
+        (0..self.height)
+            .flat_map(|row| (0..self.width).map(move |col| GridCoord::new(row, col)))
+            .filter(|&coord| self.get_cell(coord) == Some(Cell::Black))
+            .all(|coord| self.get_cell(self.mirror(coord, kind)) == Some(Cell::Black))
+    }
+
+    // the symmetry standard american crosswords need
+    pub fn is_rotationally_symmetric(&self) -> bool {
+        self.is_symmetric(SymmetryKind::Rotational)
+    }
+
+    // make the grid rotationally symmetric by blacking out the partner of every black cell
+    // any letters in those cells are lost
+    pub fn symmetrize(&mut self) {
+        for i in 0..self.cells.len() {
+            if self.cells[i].is_black() {
+                let coord = GridCoord::new(i / self.width, i % self.width);
+                let partner = self.mirror(coord, SymmetryKind::Rotational);
+                self.set_cell(partner, Cell::Black);
+            }
+        }
+        self.rebuild();
+    }
+
+    // black out cells until no entry is longer than max_len, e.g. for a venue with a length cap
+    // every cell is blacked out along with its rotational partner, so a symmetric grid stays symmetric
+    // of the cells that could break a long entry, the ones leaving the fewest runs
+    // too short to be entries are preferred, and the seed picks between those
+    // filled cells are only blacked out when an entry has no empty cells left
+    pub fn cap_word_length(&mut self, max_len: usize, seed: u64) {
+        let mut rng = seeded_rng(seed);
+        loop {
+            let long = self.entry_indices().into_iter()
+                .find(|index| self.entries[index].len() > max_len);
+            let coords = match long {
+                Some(index) => self.entries[&index].clone(),
+                None => break,
+            };
+            let empty = coords.iter()
+                .cloned()
+                .filter(|&coord| self.get_cell(coord) == Some(Cell::White(None)))
+                .collect::<Vec<_>>();
+            let choices = if empty.is_empty() { coords } else { empty };
+            let scored = choices.into_iter()
+                .map(|coord| {
+                    let mut trial = self.clone();
+                    trial.black_out_pair(coord);
+                    (trial.short_run_count(), coord)
+                })
+                .collect::<Vec<_>>();
+            let fewest = scored.iter().map(|&(count, _)| count).min().unwrap();
+            let best = scored.into_iter()
+                .filter(|&(count, _)| count == fewest)
+                .map(|(_, coord)| coord)
+                .collect::<Vec<_>>();
+            let coord = best[rng.gen_range(0, best.len())];
+            self.black_out_pair(coord);
+            self.rebuild();
+        }
+    }
+
+    // black out a cell and its rotational partner, without rebuilding the entries
+    fn black_out_pair(&mut self, coord: GridCoord) {
+        let partner = self.mirror(coord, SymmetryKind::Rotational);
+        self.set_cell(coord, Cell::Black);
+        self.set_cell(partner, Cell::Black);
+    }
+
+    // the number of runs of two or more white cells, across or down, too short to be entries
+    fn short_run_count(&self) -> usize {
+        self.rows().iter()
+            .chain(self.cols().iter())
+            .flat_map(|line| line.split(|cell| cell.is_black()))
+            .filter(|run| run.len() >= 2 && run.len() < self.min_entry_len)
+            .count()
+    }
+
+    // the grid turned a quarter turn clockwise, so it's height wide and width tall
+    // the left column becomes the top row, and across entries become down entries
+    pub fn rotate_90(&self) -> Grid {
+        let (width, height) = (self.height, self.width);
+        let cells = (0..height)
+            .flat_map(|row| (0..width).map(move |col| GridCoord::new(self.height - 1 - col, row)))
+            .map(|coord| self.get_cell(coord).unwrap())
+            .collect();
+        let mut grid = Grid {
+            cells,
+            entries: HashMap::new(),
+            perpendicular_entries: HashMap::new(),
+            width,
+            height,
+            min_entry_len: self.min_entry_len,
+        };
+        grid.rebuild();
+        grid
+    }
+
+    // the grid with rows and columns of fill cells added around its edges,
+    // e.g. Cell::Black for a bordered copy
+    pub fn pad(&self, top: usize, bottom: usize, left: usize, right: usize, fill: Cell) -> Grid {
+        let (width, height) = (self.width + left + right, self.height + top + bottom);
+        let cells = (0..height)
+            .flat_map(|row| (0..width).map(move |col| (row, col)))
+            .map(|(row, col)| {
+                let inside = (top..top + self.height).contains(&row) && (left..left + self.width).contains(&col);
+                if inside {
+                    self.get_cell(GridCoord::new(row - top, col - left)).unwrap()
+                } else {
+                    fill
+                }
+            })
+            .collect();
+        let mut grid = Grid {
+            cells,
+            entries: HashMap::new(),
+            perpendicular_entries: HashMap::new(),
+            width,
+            height,
+            min_entry_len: self.min_entry_len,
+        };
+        grid.rebuild();
+        grid
+    }
+
+    // the cell a cell is paired with under the given symmetry
+    fn mirror(&self, coord: GridCoord, kind: SymmetryKind) -> GridCoord {
+        let (row, col) = (self.height - 1 - coord.row, self.width - 1 - coord.col);
+        match kind {
+            SymmetryKind::Rotational => GridCoord::new(row, col),
+            SymmetryKind::LeftRight => GridCoord::new(coord.row, col),
+            SymmetryKind::TopBottom => GridCoord::new(row, coord.col),
+        }
+    }
+
+    // the sizes of the groups of black cells joined up across or down, largest first
+    // e.g. for flagging big black blobs that published grids avoid
+    pub fn black_cluster_sizes(&self) -> Vec<usize> {
+        let mut seen = HashSet::new();
+        let mut sizes = vec![];
+        for row in 0..self.height {
+            for col in 0..self.width {
+                let start = GridCoord::new(row, col);
+                if self.get_cell(start) != Some(Cell::Black) || !seen.insert(start) {
+                    continue;
+                }
+                // flood fill the cluster from its first cell
+                let mut size = 0;
+                let mut to_visit = vec![start];
+                while let Some(coord) = to_visit.pop() {
+                    size += 1;
+                    // get_cell would wrap a column past the right edge onto the next row
+                    let neighbors = coord.neighbors().into_iter()
+                        .filter(|n| n.row < self.height && n.col < self.width);
+                    for neighbor in neighbors {
+                        if self.get_cell(neighbor) == Some(Cell::Black) && seen.insert(neighbor) {
+                            to_visit.push(neighbor);
+                        }
+                    }
+                }
+                sizes.push(size);
+            }
+        }
+        sizes.sort_by(|a, b| b.cmp(a));
+        sizes
+    }
+
+    // the fraction of the cells that are black
+    pub fn black_cell_ratio(&self) -> f32 {
+        let black = self.cells.iter().filter(|cell| cell.is_black()).count();
+        (black as f32) / (self.cells.len() as f32)
+    }
+
+    // the average length of the entries in the grid, 0 if there aren't any
+    pub fn average_entry_len(&self) -> f32 {
+        if self.entries.is_empty() {
+            return 0f32;
+        }
+        let total: usize = self.entries.values().map(|coords| coords.len()).sum();
+        (total as f32) / (self.entries.len() as f32)
+    }
+
+    // pairs of entries that land on each other when the grid is rotated 180 degrees,
+    // sorted, with the lower index first
+    // an entry through the centre that lands on itself isn't paired
+    pub fn symmetric_entry_pairs(&self) -> Vec<(EntryIndex, EntryIndex)> {
+        let mut pairs = vec![];
+        for (&index, coords) in &self.entries {
+            // the rotated entry runs backwards, so its last cell is our first
+            let rotated = coords.iter()
+                .rev()
+                .map(|coord| GridCoord::new(self.height - 1 - coord.row, self.width - 1 - coord.col))
+                .collect::<Vec<_>>();
+            let other = self.entries.iter()
+                .find(|&(other, other_coords)| other.dir == index.dir && *other_coords == rotated)
+                .map(|(&other, _)| other);
+            if let Some(other) = other {
+                if index < other {
+                    pairs.push((index, other));
+                }
+            }
+        }
+        pairs.sort();
+        pairs
+    }
+
+    // returns all the entryindex's in the grid, sorted by number and then direction
+    // callers can rely on the order, e.g. for deterministic tie-breaking
     pub fn entry_indices(&self) -> Vec<EntryIndex> {
-        self.entries.keys().cloned().collect()
+        let mut indices = self.entries.keys().cloned().collect::<Vec<_>>();
+        indices.sort();
+        indices
     }
 
-    // returns all entry's in the grid in arbitrary order
+    // returns all entry's in the grid, in the same order as entry_indices
     pub fn entries(&self) -> Vec<Entry> {
-        self.entries.keys()
-            .map(|i| self.get_entry(*i).unwrap())
+        self.entry_indices().into_iter()
+            .map(|i| self.get_entry(i).unwrap())
             .collect()
     }
 
+    // every entry along with its index and coordinates, sorted by index
+    // e.g. for listing the clues in order
+    pub fn iter_entries(&self) -> impl Iterator<Item = (EntryIndex, Entry, &[GridCoord])> {
+        self.entry_indices().into_iter()
+            .map(move |index| (index, self.get_entry(index).unwrap(), &self.entries[&index][..]))
+    }
+
+    // the clue number of every cell that starts an entry
+    // cells are numbered left to right, top to bottom, so a cell starting
+    // both an across and a down entry has just the one number
+    pub fn clue_numbers(&self) -> HashMap<GridCoord, u32> {
+        self.entries.iter()
+            .map(|(index, coords)| (coords[0], index.num))
+            .collect()
+    }
+
+    // groups the entries by how many of their crossings would already be filled
+    // if the grid were filled in reading order, one entry at a time
+    // layer i holds the entries with i filled crossings, so a layer can be empty
+    // it's only a rough picture of how constraints pile up, since a real solve
+    // picks the most constrained entry instead
+    pub fn entry_dependency_layers(&self) -> Vec<Vec<EntryIndex>> {
+        let mut layers: Vec<Vec<EntryIndex>> = vec![];
+        let mut placed = HashSet::new();
+        for index in self.entry_indices() {
+            let mut perps = self.entries_perp_to(index);
+            perps.sort();
+            perps.dedup();
+            let crossings = perps.iter().filter(|perp| placed.contains(*perp)).count();
+            if layers.len() <= crossings {
+                layers.resize(crossings + 1, vec![]);
+            }
+            layers[crossings].push(index);
+            placed.insert(index);
+        }
+        layers
+    }
+
     // returns all the rows of the grid, where a row is a vector of cells
     pub fn rows(&self) -> Vec<Vec<Cell>> {
         (0..self.height)
@@ -244,6 +608,303 @@ impl Grid {
             .collect()
     }
 
+    // exports the grid in the crossword compiler xml format (.jpz)
+    // every cell gets its solution letter and clue number,
+    // and every entry gets a placeholder clue
+    pub fn to_jpz(&self) -> String {
+        let indices = self.entry_indices();
+        let numbers = self.clue_numbers();
+
+        let mut xml = String::new();
+        xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str("<crossword-compiler-applet xmlns=\"http://crossword.info/xml/crossword-compiler\">\n");
+        xml.push_str("<rectangular-puzzle xmlns=\"http://crossword.info/xml/rectangular-puzzle\" alphabet=\"ABCDEFGHIJKLMNOPQRSTUVWXYZ\">\n");
+        xml.push_str("<crossword>\n");
+
+        // the cells, where x is the column and y is the row, both starting at 1
+        xml.push_str(&format!("<grid width=\"{}\" height=\"{}\">\n", self.width, self.height));
+        xml.push_str("<grid-look numbering-scheme=\"normal\"/>\n");
+        for row in 0..self.height {
+            for col in 0..self.width {
+                let coord = GridCoord::new(row, col);
+                xml.push_str(&format!("<cell x=\"{}\" y=\"{}\"", col + 1, row + 1));
+                match self.get_cell(coord).unwrap() {
+                    Cell::Black => xml.push_str(" type=\"block\""),
+                    Cell::White(letter) => {
+                        if let Some(letter) = letter {
+                            xml.push_str(&format!(" solution=\"{}\"", letter));
+                        }
+                        if let Some(num) = numbers.get(&coord) {
+                            xml.push_str(&format!(" number=\"{}\"", num));
+                        }
+                    }
+                }
+                xml.push_str("></cell>\n");
+            }
+        }
+        xml.push_str("</grid>\n");
+
+        // the cells making up each entry
+        for (id, index) in indices.iter().enumerate() {
+            xml.push_str(&format!("<word id=\"{}\">\n", id + 1));
+            for coord in &self.entries[index] {
+                xml.push_str(&format!("<cells x=\"{}\" y=\"{}\"/>\n", coord.col + 1, coord.row + 1));
+            }
+            xml.push_str("</word>\n");
+        }
+
+        // placeholder clues for every entry, across first
+        for &(dir, title) in &[(EntryDir::Across, "Across"), (EntryDir::Down, "Down")] {
+            xml.push_str(&format!("<clues ordering=\"normal\">\n<title><b>{}</b></title>\n", title));
+            for (id, index) in indices.iter().enumerate() {
+                if index.dir == dir {
+                    xml.push_str(&format!("<clue word=\"{}\" number=\"{}\">{}</clue>\n", id + 1, index.num, index));
+                }
+            }
+            xml.push_str("</clues>\n");
+        }
+
+        xml.push_str("</crossword>\n");
+        xml.push_str("</rectangular-puzzle>\n");
+        xml.push_str("</crossword-compiler-applet>\n");
+        xml
+    }
+
+    // exports the grid in the open ipuz json format (.ipuz)
+    // the puzzle array has "#" for black cells and each white cell's clue number, or 0,
+    // the solution array has "#" for black cells and each white cell's letter, or null,
+    // and every entry gets a placeholder clue
+    pub fn to_ipuz(&self) -> String {
+        let numbers = self.clue_numbers();
+        let rows = |cell_json: &dyn Fn(GridCoord, Cell) -> String| {
+            (0..self.height)
+                .map(|row| {
+                    let cells = (0..self.width)
+                        .map(|col| GridCoord::new(row, col))
+                        .map(|coord| cell_json(coord, self.get_cell(coord).unwrap()))
+                        .collect::<Vec<_>>();
+                    format!("[{}]", cells.join(", "))
+                })
+                .collect::<Vec<_>>()
+                .join(",\n    ")
+        };
+        let puzzle = rows(&|coord, cell| match cell {
+            Cell::Black => "\"#\"".to_string(),
+            Cell::White(_) => numbers.get(&coord).cloned().unwrap_or(0).to_string(),
+        });
+        let solution = rows(&|_, cell| match cell {
+            Cell::Black => "\"#\"".to_string(),
+            Cell::White(Some(letter)) => format!("\"{}\"", letter),
+            Cell::White(None) => "null".to_string(),
+        });
+
+        // placeholder clues for every entry, across first
+        let indices = self.entry_indices();
+        let clues = [(EntryDir::Across, "Across"), (EntryDir::Down, "Down")].iter()
+            .map(|&(dir, title)| {
+                let list = indices.iter()
+                    .filter(|index| index.dir == dir)
+                    .map(|index| format!("[{}, \"{}\"]", index.num, index))
+                    .collect::<Vec<_>>();
+                format!("\"{}\": [{}]", title, list.join(", "))
+            })
+            .collect::<Vec<_>>();
+
+        let mut json = String::new();
+        json.push_str("{\n");
+        json.push_str("  \"version\": \"http://ipuz.org/v2\",\n");
+        json.push_str("  \"kind\": [\"http://ipuz.org/crossword#1\"],\n");
+        json.push_str(&format!("  \"dimensions\": {{\"width\": {}, \"height\": {}}},\n", self.width, self.height));
+        json.push_str("  \"block\": \"#\",\n");
+        json.push_str(&format!("  \"puzzle\": [\n    {}\n  ],\n", puzzle));
+        json.push_str(&format!("  \"solution\": [\n    {}\n  ],\n", solution));
+        json.push_str(&format!("  \"clues\": {{{}}}\n", clues.join(", ")));
+        json.push_str("}\n");
+        json
+    }
+
+    // a hash of the grid's shape and where its black cells are, e.g. for caching
+    // which templates are known to be solvable
+    // letters are left out, so the same template with different prefills has the same
+    // signature, even though the prefills may make one of them unsolvable
+    // it's fnv-1a, so it doesn't change between runs or versions of rust
+    pub fn signature(&self) -> u64 {
+        let layout = [self.width as u64, self.height as u64].iter()
+            .flat_map(|n| n.to_le_bytes().to_vec())
+            .chain(self.cells.iter().map(|cell| cell.is_black() as u8))
+            .collect::<Vec<_>>();
+        layout.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+        })
+    }
+
+    // a compact url-safe string holding the grid's cells, e.g. for sharing a grid in a link
+    // it's the height and width as two little endian bytes each,
+    // then the cells as they'd appear in a grid file, all base64 encoded
+    // none if the grid is too tall or wide for its size to fit in two bytes
+    pub fn to_token(&self) -> Option<String> {
+        let height = u16::try_from(self.height).ok()?;
+        let width = u16::try_from(self.width).ok()?;
+        let mut bytes = vec![];
+        bytes.extend(&u16_le(height));
+        bytes.extend(&u16_le(width));
+        bytes.extend(self.cells.iter().map(|cell| match *cell {
+            Cell::Black => b'#',
+            Cell::White(None) => b'.',
+            Cell::White(Some(letter)) => letter.into(),
+        }));
+        Some(base64_encode(&bytes))
+    }
+
+    // the grid a token from to_token stands for, if it's a valid token
+    pub fn from_token(token: &str) -> Option<Grid> {
+        let bytes = base64_decode(token)?;
+        if bytes.len() < 4 {
+            return None;
+        }
+        let height = (bytes[0] as usize) | (bytes[1] as usize) << 8;
+        let width = (bytes[2] as usize) | (bytes[3] as usize) << 8;
+        if bytes.len() != 4 + width * height {
+            return None;
+        }
+        let cells = bytes[4..].iter()
+            .map(|&byte| match byte {
+                b'#' | b'.' | b'A'..=b'Z' => Some(cell_from_char(byte as char)),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Grid::from_cells(&cells, width, height)
+    }
+
+    // load a Grid from an Across Lite .puz file
+    // . in the solution is a black cell, letters are filled white cells and
+    // anything else is an empty white cell
+    // a letter the player has filled in is used where the solution has none
+    pub fn from_puz<P: AsRef<Path>>(path: P) -> io::Result<Grid> {
+        let mut bytes = vec![];
+        File::open(path)?.read_to_end(&mut bytes)?;
+
+        // some files have junk before the header, so find it by its magic string
+        let start = match bytes.windows(PUZ_MAGIC.len()).position(|window| window == PUZ_MAGIC) {
+            Some(pos) if pos >= 2 => pos - 2,
+            _ => return Err(invalid_data("missing the ACROSS&DOWN magic string".to_string())),
+        };
+        let puz = &bytes[start..];
+        if puz.len() < PUZ_HEADER_LEN {
+            return Err(invalid_data("the .puz header is truncated".to_string()));
+        }
+        let (width, height) = (puz[0x2C] as usize, puz[0x2D] as usize);
+        let size = width * height;
+        if size == 0 || puz.len() < PUZ_HEADER_LEN + 2 * size {
+            return Err(invalid_data(format!("expected a {}x{} solution and fill", height, width)));
+        }
+
+        let solution = &puz[PUZ_HEADER_LEN..PUZ_HEADER_LEN + size];
+        let fill = &puz[PUZ_HEADER_LEN + size..PUZ_HEADER_LEN + 2 * size];
+        let cells = solution.iter()
+            .zip(fill)
+            .map(|(&answer, &filled)| match answer {
+                b'.' => Cell::Black,
+                _ => Cell::White(Letter::try_from(answer).or_else(|_| Letter::try_from(filled)).ok()),
+            })
+            .collect::<Vec<_>>();
+        Ok(Grid::from_cells(&cells, width, height).unwrap())
+    }
+
+    // write the grid to an Across Lite .puz file
+    // the current fill is written as the solution, with empty cells as -,
+    // and every clue is a placeholder
+    pub fn to_puz<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        File::create(path)?.write_all(&self.puz_bytes()?)
+    }
+
+    // the contents of the .puz file written by to_puz
+    fn puz_bytes(&self) -> io::Result<Vec<u8>> {
+        if self.width > 255 || self.height > 255 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      format!("a {}x{} grid is too big for a .puz file", self.height, self.width)));
+        }
+
+        let solution = self.cells.iter()
+            .map(|cell| match *cell {
+                Cell::Black => b'.',
+                Cell::White(None) => b'-',
+                Cell::White(Some(letter)) => letter.into(),
+            })
+            .collect::<Vec<u8>>();
+        // the player hasn't filled anything in yet
+        let fill = solution.iter()
+            .map(|&byte| if byte == b'.' { b'.' } else { b'-' })
+            .collect::<Vec<u8>>();
+
+        // across lite numbers any run of two or more white cells, which can include
+        // runs too short to be entries, so the clues are found the way it does
+        let white = |row: usize, col: usize| row < self.height && col < self.width && self.cells[row * self.width + col].is_white();
+        let mut clues = vec![];
+        let mut num = 1;
+        for row in 0..self.height {
+            for col in 0..self.width {
+                if !white(row, col) {
+                    continue;
+                }
+                let across = (col == 0 || !white(row, col - 1)) && white(row, col + 1);
+                let down = (row == 0 || !white(row - 1, col)) && white(row + 1, col);
+                if across {
+                    clues.push(format!("{} across", num));
+                }
+                if down {
+                    clues.push(format!("{} down", num));
+                }
+                if across || down {
+                    num += 1;
+                }
+            }
+        }
+
+        let mut header = vec![0u8; PUZ_HEADER_LEN];
+        header[0x02..0x0E].copy_from_slice(PUZ_MAGIC);
+        header[0x18..0x1C].copy_from_slice(b"1.3\0");
+        header[0x2C] = self.width as u8;
+        header[0x2D] = self.height as u8;
+        header[0x2E..0x30].copy_from_slice(&u16_le(clues.len() as u16));
+        header[0x30..0x32].copy_from_slice(&u16_le(1));
+
+        // the title, author, copyright and notes are all left empty,
+        // so only the clues count towards the text checksum
+        let cib = puz_checksum(&header[0x2C..0x34], 0);
+        let mut text = 0;
+        for clue in &clues {
+            text = puz_checksum(clue.as_bytes(), text);
+        }
+        let solution_sum = puz_checksum(&solution, 0);
+        let fill_sum = puz_checksum(&fill, 0);
+        let mut overall = puz_checksum(&fill, puz_checksum(&solution, cib));
+        for clue in &clues {
+            overall = puz_checksum(clue.as_bytes(), overall);
+        }
+        header[0x00..0x02].copy_from_slice(&u16_le(overall));
+        header[0x0E..0x10].copy_from_slice(&u16_le(cib));
+        // the masked checksums are xored with ICHEATED, low bytes first
+        let mask = b"ICHEATED";
+        for (i, &sum) in [cib, solution_sum, fill_sum, text].iter().enumerate() {
+            header[0x10 + i] = mask[i] ^ (sum & 0xFF) as u8;
+            header[0x14 + i] = mask[4 + i] ^ (sum >> 8) as u8;
+        }
+
+        let mut bytes = header;
+        bytes.extend(solution);
+        bytes.extend(fill);
+        // an empty title, author and copyright
+        bytes.extend(b"\0\0\0");
+        for clue in &clues {
+            bytes.extend(clue.as_bytes());
+            bytes.push(0);
+        }
+        // and empty notes
+        bytes.push(0);
+        Ok(bytes)
+    }
+
     // converts a coordinate to an index for the self.cells vector
     #[inline]
     fn coord_to_index(&self, coord: GridCoord) -> usize {
@@ -266,10 +927,10 @@ impl Grid {
                     },
                     Cell::Black => {
                         // if we hit a black cell, our entry stops
-                        // so check if it's at least length 3 and add it to the list
+                        // so check if it's long enough and add it to the list
                         if in_entry {
                             in_entry = false;
-                            if entry_coords.len() >= 3 {
+                            if entry_coords.len() >= self.min_entry_len {
                                 entry_coords_vec.push(entry_coords.clone());
                             }
                             entry_coords.clear();
@@ -278,7 +939,7 @@ impl Grid {
                 }
             }
             // need this final check for the end of the row
-            if in_entry && entry_coords.len() >= 3 {
+            if in_entry && entry_coords.len() >= self.min_entry_len {
                 entry_coords_vec.push(entry_coords.clone());
             }
         }
@@ -303,7 +964,7 @@ impl Grid {
                     Cell::Black => {
                         if in_entry {
                             in_entry = false;
-                            if entry_coords.len() >= 3 {
+                            if entry_coords.len() >= self.min_entry_len {
                                 entry_coords_vec.push(entry_coords.clone());
                             }
                             entry_coords.clear();
@@ -311,7 +972,7 @@ impl Grid {
                     }
                 }
             }
-            if in_entry && entry_coords.len() >= 3 {
+            if in_entry && entry_coords.len() >= self.min_entry_len {
                 entry_coords_vec.push(entry_coords.clone());
             }
         }
@@ -356,6 +1017,14 @@ impl Grid {
                 }
             }
         }
+        // get_entry, set_entry and fill_entry all zip the coords with the letters,
+        // so the coords have to be in reading order, one step apart
+        debug_assert!(self.entries.iter().all(|(index, coords)| {
+            coords.windows(2).all(|pair| match index.dir {
+                EntryDir::Across => pair[1].row == pair[0].row && pair[1].col == pair[0].col + 1,
+                EntryDir::Down => pair[1].col == pair[0].col && pair[1].row == pair[0].row + 1,
+            })
+        }));
 
         // rebuild the perpendicular entries
         // for every entry, loop over all other entries and see if they have any
@@ -363,11 +1032,12 @@ impl Grid {
         // there's probably a faster way to do this but it's not a bottleneck
         // since it's only called when the grid is initialized
         self.perpendicular_entries.clear();
-        for entry_num in self.entry_indices() {
+        let indices = self.entry_indices();
+        for &entry_num in &indices {
             let coords = self.entries[&entry_num].clone();
             let mut perpendiculars: Vec<EntryIndex> = vec![];
             for coord in coords {
-                for other_entry_num in self.entry_indices() {
+                for &other_entry_num in &indices {
                     if entry_num == other_entry_num {
                         continue;
                     }
@@ -382,11 +1052,91 @@ impl Grid {
     }
 }
 
+// reads the "height, width" header on the first line of a grid file
+fn grid_file_header(entire: &str) -> io::Result<(usize, usize)> {
+    let header = entire.lines().next().unwrap_or("");
+    let dimensions = header.split(',')
+        .map(|s| s.trim().parse::<usize>())
+        .collect::<Result<Vec<usize>, _>>();
+    match dimensions {
+        Ok(ref dims) if dims.len() == 2 && dims[0] > 0 && dims[1] > 0 => Ok((dims[0], dims[1])),
+        _ => Err(invalid_data(format!("malformed grid header {:?}, expected \"height, width\"", header))),
+    }
+}
+
+// an error for a grid file that can't be parsed
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+// every .puz file has this at offset 2
+const PUZ_MAGIC: &[u8] = b"ACROSS&DOWN\0";
+// the solution starts right after the header
+const PUZ_HEADER_LEN: usize = 0x34;
+
+// the checksum across lite uses for every section of a .puz file
+fn puz_checksum(bytes: &[u8], mut sum: u16) -> u16 {
+    for &byte in bytes {
+        sum = sum.rotate_right(1).wrapping_add(byte as u16);
+    }
+    sum
+}
+
+// the url-safe base64 alphabet
+const BASE64: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+// encodes the bytes as url-safe base64 without padding
+fn base64_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, &byte)| n | (byte as u32) << (16 - 8 * i));
+        // every byte in the chunk needs one more character to cover its bits
+        for i in 0..chunk.len() + 1 {
+            encoded.push(BASE64[(n >> (18 - 6 * i) & 0x3F) as usize] as char);
+        }
+    }
+    encoded
+}
+
+// decodes url-safe base64 without padding, none if it isn't valid
+fn base64_decode(encoded: &str) -> Option<Vec<u8>> {
+    let values = encoded.bytes()
+        .map(|c| BASE64.iter().position(|&b| b == c).map(|v| v as u32))
+        .collect::<Option<Vec<_>>>()?;
+    let mut bytes = vec![];
+    for chunk in values.chunks(4) {
+        // a single leftover character can't make up a whole byte
+        if chunk.len() == 1 {
+            return None;
+        }
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, &v)| n | v << (18 - 6 * i));
+        for i in 0..chunk.len() - 1 {
+            bytes.push((n >> (16 - 8 * i)) as u8);
+        }
+    }
+    Some(bytes)
+}
+
+// .puz files are little endian
+fn u16_le(n: u16) -> [u8; 2] {
+    [(n & 0xFF) as u8, (n >> 8) as u8]
+}
+
+// converts a character from a grid file to a cell
+// . is an empty white cell, # is a black cell and letters are filled white cells
+fn cell_from_char(c: char) -> Cell {
+    match c {
+        '.' => Cell::White(None),
+        '#' => Cell::Black,
+        e => Cell::White(Letter::try_from(e as u8).ok()),
+    }
+}
+
 impl fmt::Display for Grid {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let words = self.entries().len();
-        let average_length = 0;
-        write!(f, "{}x{}, {} words, {} average length\n", self.height, self.width, words, average_length)?;
+        let average_length = self.average_entry_len();
+        writeln!(f, "{}x{}, {} words, {:.1} average length", self.height, self.width, words, average_length)?;
         for row in 0..self.height {
             for col in 0..self.width {
                 let cell = self.get_cell((row, col).into()).unwrap();
@@ -402,250 +1152,884 @@ impl fmt::Display for Grid {
                     }
                 }
             }
-            write!(f, "\n")?;
+            writeln!(f)?;
         }
         Ok(())
     }
 }
 
+// SymmetryKind
+// the ways the black cells of a grid can mirror each other
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SymmetryKind {
+    // the grid looks the same turned upside down, i.e. rotated 180 degrees
+    Rotational,
+    // the left half mirrors the right half
+    LeftRight,
+    // the top half mirrors the bottom half
+    TopBottom,
+}
+
+// VariableOrder
+// how the solver picks which entry to fill next
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum VariableOrder {
+    // the entry with the fewest possible fills
+    #[default]
+    MostConstrained,
+    // the entry whose best possible fill has the lowest score
+    // this forces the solver to make the low quality decisions early
+    // only used when solving with a ranked dictionary
+    WorstBestScore,
+    // the entry with the lowest expected cost: its number of possible fills,
+    // times the fraction of its crossings' possible fills that would survive it on average
+    // so an entry that narrows down its crossings a lot goes before one that doesn't
+    // with the same number of fills
+    // it looks at every possible fill of every entry each time, so it's slower per step
+    ExpectedCost,
+}
+
+// Deadline
+// a point in time solves have to finish by, see GridSolver::solve_until
+// it's just an instant, so any number of solvers can share one,
+// e.g. to keep a whole batch of grids inside one time budget
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub struct Deadline(pub Instant);
+
+impl Deadline {
+    // the deadline the given time from now
+    pub fn after(duration: Duration) -> Deadline {
+        Deadline(Instant::now() + duration)
+    }
+
+    pub fn has_passed(&self) -> bool {
+        Instant::now() >= self.0
+    }
+}
+
+// SolverConfig
+// one way to set up the solver, for trying several of them with solve_portfolio
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SolverConfig {
+    // the seed for shuffling the possible fills
+    pub seed: u64,
+    // how to pick the next entry to fill
+    pub variable_order: VariableOrder,
+    // the most words to try for an entry each time it's picked, or none to try them all
+    pub branch_factor: Option<usize>,
+}
+
 // GridSolver
 // a structure that fills a grid with valid words from a dictionary
 
 #[derive(Clone, Debug)]
-pub struct GridSolver<T: UnrankedDict> {
+pub struct GridSolver<T: DictLookup> {
     // the grid being filled
     grid: Grid,
     // the dictionary in use
     dict: T,
-    // words that have been added to the grid already
-    // you can't reuse words in a fill
-    pub added_words: HashSet<Word>,
+    // the dictionary words that have been added to the grid already, and how many times
+    // you can't reuse words in a fill, but a prefilled grid might already repeat one,
+    // so a word only leaves the map once every copy of it is gone
+    used_words: HashMap<WordId, usize>,
+    // words in the grid that aren't in the dictionary, e.g. ones prefilled by hand,
+    // counted the same way
+    outside_words: HashMap<Word, usize>,
     // entries that haven't been filled yet
     unfilled_entries: HashSet<EntryIndex>,
     // for every entry, a list of words that can fill that entry
     // kept up to date as the grid is being filled
-    possible_fills: HashMap<EntryIndex, Vec<Word>>,
+    // these are the dictionary's ids, so no words are cloned to fill them,
+    // see benches/interning.rs for what this saves
+    possible_fills: HashMap<EntryIndex, Vec<WordId>>,
     // a stack that keeps tract of the changes we make to the grid
     // whenever we insert a new word
     // this allows us to easily backtrack by undoing the changes
-    changes: Vec<(EntryIndex, Word, Entry)>,
+    changes: Vec<(EntryIndex, WordId, Entry)>,
+    // how to pick the next entry to fill
+    variable_order: VariableOrder,
+    // the most words to try for an entry each time it's picked, or none to try them all
+    branch_factor: Option<usize>,
+    // whether to skip words that would leave a crossing without any possible fills
+    forward_checking: bool,
+    // whether to try words with about as many vowels as english words have first
+    vowel_balance: bool,
+    // the directions of the entries that have to be words
+    // entries in other directions are ignored, so their cells are only constrained one way
+    directions: Vec<EntryDir>,
+    // the ranked dictionary lookups, set while solving with a ranked dictionary
+    ranked: Option<RankedLookups<T>>,
+    // the lowest and highest scores a word may have to be used in a ranked solve, if limited
+    score_lower: Option<i32>,
+    score_upper: Option<i32>,
+    // in a ranked solve, words scoring under this are obscure and may not cross each other, if set
+    natick_threshold: Option<i32>,
+    // the most words any one entry may try during a solve, if limited
+    per_slot_cap: Option<usize>,
+    // how many words each entry has tried during the current solve
+    slot_attempts: HashMap<EntryIndex, usize>,
+    // the random number generator used to shuffle possible fills
+    rng: StdRng,
+    // how many dead ends an entry may hit before its crossings are cleared
+    // and re-picked, and how many times that can happen per solve
+    local_restarts: Option<(usize, usize)>,
+    // how many dead ends each entry has hit since it was last restarted
+    slot_failures: HashMap<EntryIndex, usize>,
+    // how many local restarts the current solve has used
+    restarts_used: usize,
+    // while unwinding for a local restart, the depth of the changes stack to unwind to
+    restart_depth: Option<usize>,
+    // the depth and number of words to try of every search frame still open,
+    // each of which holds its own list of them
+    open_candidates: Vec<(usize, usize)>,
+    // words that may not be used in the fill even though they're in the dictionary
+    blocklist: HashSet<WordId>,
+    // how much to add to the scores of preferred words when solving with a ranked dictionary
+    boosts: HashMap<WordId, i32>,
+    // penalties that push words further down the order they're tried in
+    soft_constraints: Vec<SoftConstraint>,
+    // words every fill has to use somewhere, e.g. theme answers
+    required_words: Vec<Word>,
+    // statistics about the current or last solve
+    stats: SolveStats,
+    // called with the stats every so many nodes, if set
+    checkpoint: Option<Checkpoint>,
+    // the most fills and undos the current solve may make, if limited
+    step_limit: Option<u64>,
+    // when the current solve has to stop, if limited
+    deadline: Option<Deadline>,
+    // whether the current solve stopped because it ran out of steps or time
+    out_of_steps: bool,
+    // the deepest entry the current solve found without any possible fills,
+    // along with how many words were in the grid at the time
+    dead_end: Option<(usize, EntryIndex)>,
+    // when the current or last solve started
+    solve_started: Instant,
+    // the grid at the deepest point of the current solve, while solve_best_effort is tracking it
+    deepest_fill: Option<Grid>,
 }
 
-impl<T: UnrankedDict> GridSolver<T> {
-    // construct a new gridsolver for the given grid with the given dictionary
-    pub fn new(grid: Grid, dict: T) -> GridSolver<T> {
-        let mut solver = GridSolver {
-            grid: grid,
-            dict: dict,
-            added_words: HashSet::new(),
-            unfilled_entries: HashSet::new(),
-            possible_fills: HashMap::new(),
-            changes: vec![],
-        };
+// FillError
+// why Grid::fill_entry couldn't fill an entry
 
-        // all entries are initially unsolved
-        for index in solver.grid.entry_indices() {
-            if solver.grid.is_entry_filled(index) {
-                let entry = solver.grid.get_entry(index).unwrap();
-                let letters = entry.letters.into_iter().filter_map(|x| x).collect::<Vec<_>>();
-                solver.added_words.insert(Word::new(&letters));
-            }
-            else {
-                solver.update_possible_fills(index);
-                solver.unfilled_entries.insert(index);
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FillError {
+    // the grid has no entry with that index
+    NoSuchEntry,
+    // the word is a different length from the entry
+    LengthMismatch { entry: usize, word: usize },
+}
+
+impl fmt::Display for FillError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FillError::NoSuchEntry => write!(f, "there's no such entry"),
+            FillError::LengthMismatch { entry, word } => {
+                write!(f, "a {} letter word can't fill a {} letter entry", word, entry)
             }
         }
+    }
+}
 
-        solver
+impl ::std::error::Error for FillError {}
+
+// GridSolverBuilder
+// every solver setting in one place, for when GridSolver::new and a few setters won't do
+// settings that aren't given keep the defaults GridSolver::new uses
+// e.g. GridSolverBuilder::new().seed(7).branch_factor(10).build(grid, dict)
+
+#[derive(Clone, Debug, Default)]
+pub struct GridSolverBuilder {
+    seed: Option<u64>,
+    branch_factor: Option<usize>,
+    score_range: Option<(Option<i32>, Option<i32>)>,
+    variable_order: Option<VariableOrder>,
+    forward_checking: bool,
+    blocked: Vec<Word>,
+    required: Vec<Word>,
+}
+
+impl GridSolverBuilder {
+    pub fn new() -> GridSolverBuilder {
+        GridSolverBuilder::default()
     }
 
-    // update the list of possible words for a given index
-    fn update_possible_fills(&mut self, index: EntryIndex) {
-        // get the entry from the grid
-        let opt_entry = self.grid.get_entry(index);
-        match opt_entry {
-            Some(entry) => {
-                // make a pattern fitting the entry
-                // and update the possible fill words
-                let pattern = Pattern::new(&entry.letters);
-                let fills = self.dict.lookup(&pattern);
-                self.possible_fills.insert(index, fills);
-            }
-            _ => {},
-        };
+    // see GridSolver::with_seed
+    pub fn seed(mut self, seed: u64) -> GridSolverBuilder {
+        self.seed = Some(seed);
+        self
     }
 
-    // fill the given entry with the given word
-    fn fill(&mut self, index: EntryIndex, word: &Word) {
-        // push the index we're changing as well as a copy of the entry before
-        // we insert the word onto the changes stack
-        self.changes.push((index, word.clone(), self.grid.get_entry(index).unwrap()));
-        // fill the entry and remove the index from unfilled_entries
-        self.grid.fill_entry(index, word);
-        self.unfilled_entries.remove(&index);
-        self.added_words.insert(word.clone());
-        // update the possible words for the intersecting entries
-        for perp in self.grid.entries_perp_to(index) {
-            self.update_possible_fills(perp);
+    // see GridSolver::set_branch_factor
+    pub fn branch_factor(mut self, factor: usize) -> GridSolverBuilder {
+        self.branch_factor = Some(factor);
+        self
+    }
+
+    // see GridSolver::set_score_range
+    pub fn score_range(mut self, lower: Option<i32>, upper: Option<i32>) -> GridSolverBuilder {
+        self.score_range = Some((lower, upper));
+        self
+    }
+
+    // see GridSolver::set_variable_order
+    pub fn variable_order(mut self, order: VariableOrder) -> GridSolverBuilder {
+        self.variable_order = Some(order);
+        self
+    }
+
+    // see GridSolver::set_forward_checking
+    pub fn forward_checking(mut self, enabled: bool) -> GridSolverBuilder {
+        self.forward_checking = enabled;
+        self
+    }
+
+    // see GridSolver::add_blocked
+    pub fn block(mut self, word: Word) -> GridSolverBuilder {
+        self.blocked.push(word);
+        self
+    }
+
+    // see GridSolver::require_word
+    // a word that can't go anywhere in the grid isn't required
+    pub fn require(mut self, word: Word) -> GridSolverBuilder {
+        self.required.push(word);
+        self
+    }
+
+    // a solver for the grid and dictionary with these settings
+    pub fn build<T: DictLookup>(self, grid: Grid, dict: T) -> GridSolver<T> {
+        let mut solver = GridSolver::new(grid, dict);
+        if let Some(seed) = self.seed {
+            solver.reseed(seed);
         }
+        if let Some(factor) = self.branch_factor {
+            solver.set_branch_factor(Some(factor));
+        }
+        if let Some((lower, upper)) = self.score_range {
+            solver.set_score_range(lower, upper);
+        }
+        if let Some(order) = self.variable_order {
+            solver.set_variable_order(order);
+        }
+        solver.set_forward_checking(self.forward_checking);
+        for word in &self.blocked {
+            solver.add_blocked(word);
+        }
+        for word in &self.required {
+            if !solver.require_word(word) {
+                warn!("the required word {} can't go anywhere in the grid", word);
+            }
+        }
+        solver
     }
+}
 
-    // undo filling the last entry
-    fn undo_last_fill(&mut self) {
-        // no changes = nothing to undo
-        if self.changes.is_empty() {
-            return;
+// SolveOutcome
+// how a solve with a step budget ended
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SolveOutcome {
+    // the grid is filled
+    Solved,
+    // the search ran out of words to try, so the grid can't be filled
+    Unsolvable,
+    // the search ran out of steps before it finished
+    BudgetExhausted,
+}
+
+// Rejection
+// why a word can't go in an entry, see GridSolver::explain_rejection
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Rejection {
+    // the word is a different length from the entry, or there's no such entry
+    LengthMismatch,
+    // the word disagrees with the letter already at this position of the entry
+    PatternConflict(usize),
+    // the word isn't in the dictionary
+    NotInDictionary,
+    // the word is somewhere else in the grid already
+    AlreadyUsed,
+    // the word was blocked with add_blocked
+    Blacklisted,
+    // no word could fill this crossing entry with the word's letters in it
+    CrossingDeadEnd(EntryIndex),
+}
+
+// SolveStats
+// how much work a solve did
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct SolveStats {
+    // the number of words the search has put into the grid
+    pub nodes: usize,
+    // the number of words it had to take back out
+    pub backtracks: usize,
+    // the most words the search had in the grid at once
+    pub max_depth: usize,
+    // how long the solve had been running at its last fill or undo
+    pub elapsed: Duration,
+    // the most words held at once in all the entries' lists of possible fills
+    // and the lists of words the search still had to try at each depth,
+    // a rough measure of how much memory the search needed
+    pub peak_possible_fills_total: usize,
+}
+
+// Checkpoint
+// a progress callback and how many nodes apart it's called
+// the callback is shared, so a cloned solver reports to the same place
+
+#[derive(Clone)]
+struct Checkpoint {
+    every: usize,
+    callback: Rc<RefCell<CheckpointCallback>>,
+}
+
+type CheckpointCallback = Box<dyn FnMut(&SolveStats)>;
+
+impl fmt::Debug for Checkpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Checkpoint every {} nodes", self.every)
+    }
+}
+
+// SoftConstraint
+// a penalty for putting a word in an entry, see add_soft_constraint
+// the function is shared, so a cloned solver uses the same one
+
+#[derive(Clone)]
+struct SoftConstraint(Rc<SoftConstraintFn>);
+
+pub type SoftConstraintFn = Box<dyn Fn(&Word, EntryIndex) -> i32>;
+
+impl fmt::Debug for SoftConstraint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SoftConstraint")
+    }
+}
+
+// RankedLookups
+// the search only needs a DictLookup, so when solving with a ranked
+// dictionary the ranked functions it uses are handed over as plain function pointers
+
+struct RankedLookups<T> {
+    lookup_range: fn(&T, &Pattern, Option<i32>, Option<i32>) -> Vec<WordId>,
+    score: fn(&T, WordId) -> Option<i32>,
+}
+
+// derived impls would require T: Clone and T: Debug
+impl<T> Clone for RankedLookups<T> {
+    fn clone(&self) -> RankedLookups<T> {
+        *self
+    }
+}
+
+impl<T> Copy for RankedLookups<T> {}
+
+impl<T> fmt::Debug for RankedLookups<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "RankedLookups")
+    }
+}
+
+impl<T: DictLookup> GridSolver<T> {
+    // construct a new gridsolver for the given grid with the given dictionary
+    pub fn new(grid: Grid, dict: T) -> GridSolver<T> {
+        let mut solver = GridSolver {
+            grid,
+            dict,
+            used_words: HashMap::new(),
+            outside_words: HashMap::new(),
+            unfilled_entries: HashSet::new(),
+            possible_fills: HashMap::new(),
+            changes: vec![],
+            variable_order: VariableOrder::default(),
+            branch_factor: None,
+            forward_checking: false,
+            vowel_balance: false,
+            directions: vec![EntryDir::Across, EntryDir::Down],
+            ranked: None,
+            score_lower: Some(40),
+            score_upper: None,
+            natick_threshold: None,
+            per_slot_cap: None,
+            slot_attempts: HashMap::new(),
+            rng: seeded_rng(thread_rng().gen()),
+            local_restarts: None,
+            slot_failures: HashMap::new(),
+            restarts_used: 0,
+            restart_depth: None,
+            open_candidates: vec![],
+            blocklist: HashSet::new(),
+            boosts: HashMap::new(),
+            soft_constraints: vec![],
+            required_words: vec![],
+            stats: SolveStats::default(),
+            checkpoint: None,
+            step_limit: None,
+            deadline: None,
+            out_of_steps: false,
+            dead_end: None,
+            solve_started: Instant::now(),
+            deepest_fill: None,
+        };
+
+        solver.sync_with_grid();
+        for index in solver.check_prefill() {
+            warn!("no word in the dictionary fits the letters already in {}", index);
         }
-        // set the entry to what it was beforehand
-        let (index, prev_word, prev_entry) = self.changes.pop().unwrap();
-        self.grid.set_entry(index, &prev_entry);
-        // the entry is now unfilled
-        self.unfilled_entries.insert(index);
-        self.added_words.remove(&prev_word);
-        // update the possible words for both the index and all intersecting indices
-        self.update_possible_fills(index);
-        for perp in self.grid.entries_perp_to(index) {
-            self.update_possible_fills(perp);
+
+        solver
+    }
+
+    // like new, but the possible fills are shuffled with the given seed instead of a random one
+    // so the same grid, dictionary and seed always give the same fill
+    pub fn with_seed(grid: Grid, dict: T, seed: u64) -> GridSolver<T> {
+        let mut solver = GridSolver::new(grid, dict);
+        solver.reseed(seed);
+        solver
+    }
+
+    // work out which entries are filled and what can go in the rest from the grid itself
+    // the changes stack is dropped, since it may no longer match the grid
+    fn sync_with_grid(&mut self) {
+        self.changes.clear();
+        self.unfilled_entries.clear();
+        self.possible_fills.clear();
+        self.count_words();
+        for index in self.grid.entry_indices() {
+            if !self.is_ignored(index) && !self.grid.is_entry_filled(index) {
+                self.update_possible_fills(index);
+                self.unfilled_entries.insert(index);
+            }
         }
     }
 
-    // fill the grid completely
-    // returns true if it's filled, false otherwise
-    pub fn solve(&mut self) -> bool {
-        // if there are no unfilled entries, we're done
-        if self.unfilled_entries.is_empty() {
-            return true;
+    // count the words in the grid from scratch
+    fn count_words(&mut self) {
+        self.used_words.clear();
+        self.outside_words.clear();
+        for index in self.grid.entry_indices() {
+            if self.is_ignored(index) {
+                continue;
+            }
+            if let Some(word) = self.entry_word(index) {
+                self.add_word(&word);
+            }
         }
-        
-        // find entry with the least number of possible fills
-        let most_constrained = self.unfilled_entries.iter()
-            .min_by_key(|index| self.possible_fills.get(index).unwrap().len())
-            .unwrap()
-            .clone();
+    }
 
-        // if there are zero possible fills, the grid cannot be filled
-        let mut possibilities: Vec<Word> = self.possible_fills[&most_constrained].clone();
-        if possibilities.is_empty() {
-            return false;
+    // start over on a different grid, keeping the dictionary and settings
+    // e.g. to go on to the next grid in a batch without loading the dictionary again
+    pub fn new_grid(&mut self, grid: Grid) {
+        self.grid = grid;
+        self.sync_with_grid();
+        for index in self.check_prefill() {
+            warn!("no word in the dictionary fits the letters already in {}", index);
+        }
+    }
+
+    // put the solver back the way it was when it was made
+    // every word it filled in is erased, leaving only the prefilled letters
+    pub fn reset(&mut self) {
+        while let Some((index, _, prev_entry)) = self.changes.pop() {
+            self.grid.set_entry(index, &prev_entry);
         }
+        self.stats = SolveStats::default();
+        self.sync_with_grid();
+    }
 
-        // shuffle the possibile words
-        let mut rng = thread_rng();
-        rng.shuffle(&mut possibilities);
+    // a compact token for the grid in its current state, see Grid::to_token
+    pub fn to_token(&self) -> Option<String> {
+        self.grid.to_token()
+    }
 
-        // try a different number of possible words based on the length of the words
-        // this is completely arbitrary
-        let word_len = possibilities[0].size();
-        let to_take: usize = if word_len > 8 { 5 } else if word_len > 4 { 5 } else { 5 };
+    // the grid being filled
+    pub fn grid(&self) -> &Grid {
+        &self.grid
+    }
 
-        let possibilities = possibilities.into_iter()
-            .take(to_take)
-            .collect::<Vec<_>>();
+    // every entry as it stands, filled or not, in clue order
+    // e.g. for showing the answers so far, blanks and all
+    pub fn current_entries(&self) -> Vec<(EntryIndex, Entry)> {
+        self.grid.iter_entries()
+            .map(|(index, entry, _)| (index, entry))
+            .collect()
+    }
 
-        // for each word to try, insert that word and recursively try filling the grid
-        for word in &possibilities {
-            // let score = self.dict.get_score(&word).unwrap();
-            self.fill(most_constrained, word);
-            if self.solve() {
-                return true;
+    // the shannon entropy, in bits, of the letters in the words in the grid
+    // a fill that uses lots of different letters evenly scores higher than
+    // one that keeps repeating the same few, and an empty grid scores 0
+    pub fn fill_entropy(&self) -> f64 {
+        let mut counts = HashMap::new();
+        let mut total = 0;
+        for (word, copies) in self.grid_words() {
+            for &letter in &word.letters {
+                *counts.entry(letter).or_insert(0) += copies;
+                total += copies;
             }
-            self.undo_last_fill();
         }
+        counts.values()
+            .map(|&count| {
+                let p = count as f64 / total as f64;
+                p * (1.0 / p).log2()
+            })
+            .sum()
+    }
 
-        // if none of the words work we can't fill the grid
-        false
+    // the dictionary in use
+    pub fn dict(&self) -> &T {
+        &self.dict
     }
-}
 
-impl<T: RankedDict> GridSolver<T> {
-    pub fn average_score(&self) -> f32 {
-        let mut score = 0;
-        for word in &self.added_words {
-            score += self.dict.get_score(&word).unwrap_or(0);
+    // recompute the possible fills of every unfilled entry
+    // the possible fills are cached lookups, so they go stale whenever the dictionary changes,
+    // and so does which of the words in the grid are dictionary words
+    pub fn dictionary_changed(&mut self) {
+        self.count_words();
+        for index in self.unfilled_entries.clone() {
+            self.update_possible_fills(index);
         }
-        if !self.added_words.is_empty() {
-            (score as f32) / (self.added_words.len() as f32)
-        } else {
-            0f32
+    }
+
+    // set how the solver picks which entry to fill next
+    pub fn set_variable_order(&mut self, order: VariableOrder) {
+        self.variable_order = order;
+    }
+
+    // only fill entries in the given directions, e.g. just across for an across-only puzzle
+    // entries in the other directions are ignored, so they don't have to be words
+    // the undo history is dropped
+    pub fn set_directions(&mut self, dirs: &[EntryDir]) {
+        self.directions = dirs.to_vec();
+        self.sync_with_grid();
+    }
+
+    // whether an entry is in a direction the solver ignores
+    fn is_ignored(&self, index: EntryIndex) -> bool {
+        !self.directions.contains(&index.dir)
+    }
+
+    // only use words scoring between lower and upper, inclusive, when solving with a ranked dictionary
+    // none leaves that end open, and by default only words scoring under 40 are left out
+    // too high a floor can leave some entry without any words, making the grid unsolvable
+    pub fn set_score_range(&mut self, lower: Option<i32>, upper: Option<i32>) {
+        self.score_lower = lower;
+        self.score_upper = upper;
+        self.dictionary_changed();
+    }
+
+    // check each word against its crossings before putting it in the grid, off by default
+    // a word that would leave some crossing with no possible fills is skipped
+    // instead of being filled and undone, so the search backtracks less,
+    // but every word tried costs a lookup per crossing
+    // the fills found are the same, only the work done to find them changes
+    pub fn set_forward_checking(&mut self, enabled: bool) {
+        self.forward_checking = enabled;
+    }
+
+    // try words with a balanced mix of vowels and consonants first, off by default
+    // this steers the fill away from clusters like TSKTSK that make crossings hard
+    // a ranked solve still tries better scoring words first, counting the boosts
+    // from prefer_words, so only ties are reordered
+    pub fn set_vowel_balance(&mut self, enabled: bool) {
+        self.vowel_balance = enabled;
+    }
+
+    // set how many words to try for an entry each time it's picked
+    // none, the default, tries every possible word
+    // a small factor, e.g. Some(5), trades completeness for speed: the solver
+    // can give up on grids that can be filled because it never tried the right word
+    pub fn set_branch_factor(&mut self, factor: Option<usize>) {
+        self.branch_factor = factor;
+    }
+
+    // limit how many words any single entry may try over a whole solve
+    // once an entry has used up its attempts, the solver backtracks past it
+    // this stops the search from thrashing on one pathological entry
+    pub fn set_per_slot_cap(&mut self, cap: usize) {
+        self.per_slot_cap = Some(cap);
+    }
+
+    // reseed the random number generator used to shuffle possible fills
+    pub fn reseed(&mut self, seed: u64) {
+        self.rng = seeded_rng(seed);
+    }
+
+    // when an entry hits more than threshold dead ends, clear the crossing
+    // entries filled before it and re-pick their words with a fresh shuffle
+    // this escapes a bad region without restarting the whole solve
+    // at most max_restarts of these happen per solve
+    // only used by solve and solve_ranked
+    pub fn set_local_restarts(&mut self, threshold: usize, max_restarts: usize) {
+        self.local_restarts = Some((threshold, max_restarts));
+    }
+
+    // call the callback with the current stats every `every` nodes while solving
+    // an every of 0 never calls it
+    // this is cheap enough for progress bars and logging on long solves
+    pub fn set_checkpoint(&mut self, every: usize, callback: Box<dyn FnMut(&SolveStats)>) {
+        self.checkpoint = Some(Checkpoint {
+            every,
+            callback: Rc::new(RefCell::new(callback)),
+        });
+    }
+
+    // statistics about the current or last solve
+    pub fn stats(&self) -> &SolveStats {
+        &self.stats
+    }
+
+    // forbid a word from being used in the fill
+    // words already in the grid stay where they are
+    // a word that isn't in the dictionary could never be used, so there's nothing to block
+    pub fn add_blocked(&mut self, word: &Word) {
+        if let Some(id) = self.dict.word_id(word) {
+            self.blocklist.insert(id);
+            for fills in self.possible_fills.values_mut() {
+                fills.retain(|&fill| fill != id);
+            }
         }
     }
 
-    fn update_possible_fills_ranked(&mut self, index: EntryIndex) {
-        // get the entry from the grid
-        let opt_entry = self.grid.get_entry(index);
-        match opt_entry {
-            Some(entry) => {
-                // make a pattern fitting the entry
-                // and update the possible fill words
-                let pattern = Pattern::new(&entry.letters);
-                let fills = self.dict.lookup_range(&pattern, Some(40), None);
-                self.possible_fills.insert(index, fills);
+    // when solving with a ranked dictionary, rank the given words as if
+    // their scores were boost higher, so they're placed wherever they fit if possible
+    // e.g. a family of theme words
+    // the boost lasts until clear_preferred_words is called
+    // words that aren't in the dictionary are left out
+    pub fn prefer_words(&mut self, words: &[Word], boost: i32) {
+        for word in words {
+            if let Some(id) = self.dict.word_id(word) {
+                self.boosts.insert(id, boost);
             }
-            _ => {},
-        };
+        }
+        self.dictionary_changed();
     }
 
-    pub fn solve_ranked(&mut self) -> bool {
-        // if there are no unfilled entries, we're done
-        if self.unfilled_entries.is_empty() {
-            return true;
+    // stop boosting the words passed to prefer_words
+    pub fn clear_preferred_words(&mut self) {
+        self.boosts.clear();
+        self.dictionary_changed();
+    }
+
+    // penalize words without ruling them out
+    // the penalty f gives a word in an entry is taken off its score, so it's tried later,
+    // and the penalties of every soft constraint add up
+    // without a ranked dictionary every word starts from the same score
+    // e.g. |word, _| if word.letters.contains(&q) { 10 } else { 0 } to put off words with a Q
+    pub fn add_soft_constraint(&mut self, f: SoftConstraintFn) {
+        self.soft_constraints.push(SoftConstraint(Rc::new(f)));
+    }
+
+    // make every fill use the word somewhere
+    // entries try it before any other word, and a fill without it counts as a dead end
+    // the word has to be in the dictionary, and with a ranked one inside the score range
+    // returns false, and doesn't require the word, if it can't go in any entry as things stand
+    pub fn require_word(&mut self, word: &Word) -> bool {
+        let fits = self.word_uses(word) > 0 || self.grid.entry_indices()
+            .into_iter()
+            .filter(|&index| !self.is_ignored(index) && !self.grid.is_entry_filled(index))
+            .any(|index| self.explain_rejection(index, word).is_none());
+        if fits && !self.required_words.contains(word) {
+            self.required_words.push(word.clone());
         }
-        
-        // find entry with the least number of possible fills
-        let most_constrained = self.unfilled_entries.iter()
-            .min_by_key(|index| self.possible_fills.get(index).unwrap().len())
-            .unwrap()
-            .clone();
+        fits
+    }
 
-        // if there are zero possible fills, the grid cannot be filled
-        let mut possibilities: Vec<Word> = self.possible_fills[&most_constrained].clone();
-        if possibilities.is_empty() {
-            return false;
+    // put the word in the given entry for good, e.g. a theme answer
+    // it's treated like a prefilled word, so backtracking never takes it out
+    // if it can't go there the grid is left alone and the reason is returned
+    // the undo history is dropped
+    pub fn require_at(&mut self, index: EntryIndex, word: &Word) -> Result<(), Rejection> {
+        if let Some(rejection) = self.explain_rejection(index, word) {
+            return Err(rejection);
         }
+        self.grid.fill_entry(index, word).expect("explain_rejection checked the word fits");
+        self.sync_with_grid();
+        Ok(())
+    }
 
-        // shuffle the possibile words
-        // let mut rng = thread_rng();
-        // rng.shuffle(&mut possibilities);
+    // whether a complete fill is still missing one of the required words
+    fn missing_required_words(&self) -> bool {
+        self.required_words.iter().any(|word| self.word_uses(word) == 0)
+    }
+
+    // the words in the grid, and how many times each is used
+    pub fn added_words(&self) -> HashMap<Word, usize> {
+        self.grid_words()
+            .map(|(word, count)| (word.clone(), count))
+            .collect()
+    }
+
+    // the words in the grid, and how many times each is used, without cloning them
+    fn grid_words(&self) -> impl Iterator<Item = (&Word, usize)> {
+        self.used_words.iter()
+            .map(move |(&id, &count)| (self.dict.word(id), count))
+            .chain(self.outside_words.iter().map(|(word, &count)| (word, count)))
+    }
+
+    // how many times the word is used in the grid
+    fn word_uses(&self, word: &Word) -> usize {
+        let used = self.dict.word_id(word).and_then(|id| self.used_words.get(&id));
+        used.or_else(|| self.outside_words.get(word)).cloned().unwrap_or(0)
+    }
+
+    // count another copy of the word in the grid
+    fn add_word(&mut self, word: &Word) {
+        match self.dict.word_id(word) {
+            Some(id) => *self.used_words.entry(id).or_insert(0) += 1,
+            None => *self.outside_words.entry(word.clone()).or_insert(0) += 1,
+        }
+    }
 
-        // try a different number of possible words based on the length of the words
-        // this is completely arbitrary
-        let word_len = possibilities[0].size();
-        let to_take: usize = if word_len > 8 { 5 } else if word_len > 4 { 5 } else { 5 };
+    // count one less copy of the word in the grid, forgetting it once there are none
+    fn remove_word(&mut self, word: &Word) {
+        match self.dict.word_id(word) {
+            Some(id) if self.used_words.contains_key(&id) => forget_one(&mut self.used_words, &id),
+            _ => forget_one(&mut self.outside_words, word),
+        }
+    }
 
-        let possibilities = possibilities.into_iter()
-            .take(to_take)
+    // the word in the given entry, if it's completely filled
+    fn entry_word(&self, index: EntryIndex) -> Option<Word> {
+        if !self.grid.is_entry_filled(index) {
+            return None;
+        }
+        let letters = self.grid.get_entry(index).unwrap().letters.into_iter()
+            .flatten()
             .collect::<Vec<_>>();
+        Some(Word::new(&letters))
+    }
 
-        // for each word to try, insert that word and recursively try filling the grid
-        for word in &possibilities {
-            // let score = self.dict.get_score(&word).unwrap();
-            self.fill_ranked(most_constrained, word);
-            if self.solve_ranked() {
-                return true;
+    // update the list of possible words for a given index
+    fn update_possible_fills(&mut self, index: EntryIndex) {
+        if self.is_ignored(index) {
+            return;
+        }
+        // make a pattern fitting the entry in the grid
+        // and update the possible fill words
+        if let Some(pattern) = self.grid.entry_pattern(index) {
+            let fills = self.lookup_fills(&pattern);
+            self.possible_fills.insert(index, fills);
+        }
+    }
+
+    // the words the solver may use for the pattern, in the order it should try them
+    fn lookup_fills(&self, pattern: &Pattern) -> Vec<WordId> {
+        let blocklist = &self.blocklist;
+        let dict = &self.dict;
+        let boosts = &self.boosts;
+        match self.ranked {
+            Some(ranked) => {
+                let (lower, upper) = (self.score_lower, self.score_upper);
+                let mut ids = (ranked.lookup_range)(dict, pattern, lower, upper);
+                if !boosts.is_empty() {
+                    // preferred words are ranked by their boosted score,
+                    // which can lift them over the floor
+                    let score = |id: WordId| {
+                        (ranked.score)(dict, id).unwrap_or(0) + boosts.get(&id).cloned().unwrap_or(0)
+                    };
+                    if let Some(floor) = lower {
+                        for &id in boosts.keys() {
+                            let base = (ranked.score)(dict, id);
+                            let lifted = base.is_some_and(|base| base < floor && upper.is_none_or(|upper| base <= upper));
+                            if lifted && score(id) >= floor && dict.id_matches(id, pattern) {
+                                ids.push(id);
+                            }
+                        }
+                    }
+                    ids.sort_by_key(|&id| -score(id));
+                }
+                ids.retain(|id| !blocklist.contains(id));
+                ids
+            }
+            None => {
+                dict.lookup_ids(pattern)
+                    .filter(|id| !blocklist.contains(id))
+                    .collect()
             }
-            self.undo_last_fill_ranked();
         }
+    }
 
-        // if none of the words work we can't fill the grid
-        false
+    // the pattern the crossing entry would have with the word in the given entry
+    fn crossing_pattern(&self, index: EntryIndex, word: &Word, perp: EntryIndex) -> Pattern {
+        let coords = self.grid.get_entry_coords(index).unwrap();
+        let perp_coords = self.grid.get_entry_coords(perp).unwrap();
+        let mut letters = self.grid.get_entry(perp).unwrap().letters;
+        for (pos, coord) in coords.iter().enumerate() {
+            if let Some(perp_pos) = perp_coords.iter().position(|c| c == coord) {
+                letters[perp_pos] = Some(word.letters[pos]);
+            }
+        }
+        Pattern::new(&letters)
+    }
+
+    // whether forward checking rules out the word for the entry
+    // because it would leave some unfilled crossing without any possible fills
+    fn wipes_out_a_crossing(&mut self, index: EntryIndex, id: WordId) -> bool {
+        if !self.forward_checking {
+            return false;
+        }
+        let word = self.dict.word(id);
+        let mut perps = self.grid.entries_perp_to(index);
+        perps.sort();
+        perps.dedup();
+        perps.retain(|perp| self.unfilled_entries.contains(perp) && !self.is_ignored(*perp));
+        perps.into_iter().any(|perp| {
+            let pattern = self.crossing_pattern(index, word, perp);
+            self.lookup_fills(&pattern).is_empty()
+        })
+    }
+
+    // switch between ranked and unranked solving
+    // the possible fills depend on the mode, so they're recomputed when it changes
+    fn set_ranked(&mut self, ranked: Option<RankedLookups<T>>) {
+        let changed = self.ranked.is_some() != ranked.is_some();
+        self.ranked = ranked;
+        if changed {
+            for index in self.unfilled_entries.clone() {
+                self.update_possible_fills(index);
+            }
+        }
     }
 
-    fn fill_ranked(&mut self, index: EntryIndex, word: &Word) {
+    // fill the given entry with the given word
+    fn fill(&mut self, index: EntryIndex, id: WordId) {
         // push the index we're changing as well as a copy of the entry before
         // we insert the word onto the changes stack
-        self.changes.push((index, word.clone(), self.grid.get_entry(index).unwrap()));
+        self.changes.push((index, id, self.grid.get_entry(index).unwrap()));
         // fill the entry and remove the index from unfilled_entries
-        self.grid.fill_entry(index, word);
+        // possible fills always match their entry's pattern, so they're the right length
+        self.grid.fill_entry(index, self.dict.word(id)).expect("possible fills fit their entry");
         self.unfilled_entries.remove(&index);
-        self.added_words.insert(word.clone());
+        *self.used_words.entry(id).or_insert(0) += 1;
         // update the possible words for the intersecting entries
-        self.update_possible_fills_ranked(index);
         for perp in self.grid.entries_perp_to(index) {
             self.update_possible_fills(perp);
         }
+
+        self.stats.nodes += 1;
+        if self.changes.len() > self.stats.max_depth {
+            if let Some(ref mut deepest) = self.deepest_fill {
+                deepest.clone_from(&self.grid);
+            }
+        }
+        self.stats.max_depth = self.stats.max_depth.max(self.changes.len());
+        self.stats.elapsed = self.solve_started.elapsed();
+        if let Some(ref checkpoint) = self.checkpoint {
+            if self.stats.nodes.is_multiple_of(checkpoint.every) {
+                (checkpoint.callback.borrow_mut())(&self.stats);
+            }
+        }
     }
 
-    fn undo_last_fill_ranked(&mut self) {
+    // undo filling the last entry
+    fn undo_last_fill(&mut self) {
         // no changes = nothing to undo
         if self.changes.is_empty() {
             return;
@@ -655,34 +2039,2578 @@ impl<T: RankedDict> GridSolver<T> {
         self.grid.set_entry(index, &prev_entry);
         // the entry is now unfilled
         self.unfilled_entries.insert(index);
-        self.added_words.remove(&prev_word);
+        if self.used_words.contains_key(&prev_word) {
+            forget_one(&mut self.used_words, &prev_word);
+        } else {
+            // the dictionary changed since, so the word is counted by its letters
+            let prev_word = self.dict.word(prev_word).clone();
+            self.remove_word(&prev_word);
+        }
         // update the possible words for both the index and all intersecting indices
-        self.update_possible_fills_ranked(index);
+        self.update_possible_fills(index);
         for perp in self.grid.entries_perp_to(index) {
-            self.update_possible_fills_ranked(perp);
+            self.update_possible_fills(perp);
         }
+        self.stats.backtracks += 1;
+        self.stats.elapsed = self.solve_started.elapsed();
     }
-}
 
-impl<T: UnrankedDict> fmt::Display for GridSolver<T> {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.grid)?;
-        let mut added_words = self.added_words.iter().cloned().collect::<Vec<_>>();
-        if added_words.is_empty() {
-            return write!(f, "no words added yet\n");
-        } else {
-            write!(f, "number of words: {}\n", self.added_words.len())?;
+    // replace the word in an entry, e.g. when the user edits a solved grid by hand
+    // if any crossing entries can no longer be filled with a dictionary word,
+    // the grid is left unchanged and those entries are returned
+    // if the word doesn't fit the entry at all, or isn't an allowed dictionary word,
+    // the entry itself is returned
+    // the undo history is dropped, since it no longer matches the grid
+    pub fn replace_word(&mut self, index: EntryIndex, word: &Word) -> Result<(), Vec<EntryIndex>> {
+        match self.dict.word_id(word) {
+            Some(id) if !self.blocklist.contains(&id) => {}
+            _ => return Err(vec![index]),
         }
-        added_words.sort_by_key(|word| word.size());
-        let mut prev_word_size = added_words.get(0).unwrap().size();
-        for word in &added_words {
-            if prev_word_size < word.size() {
-                write!(f, "\n")?;
-                prev_word_size = word.size();
+        let prev_entry = match self.grid.get_entry(index) {
+            Some(ref entry) if entry.letters.len() == word.size() && !self.is_ignored(index) => entry.clone(),
+            _ => return Err(vec![index]),
+        };
+        let mut affected = self.grid.entries_perp_to(index);
+        affected.retain(|&perp| !self.is_ignored(perp));
+        affected.sort();
+        affected.dedup();
+
+        // the words in the affected entries are about to change
+        let prev_words = affected.iter()
+            .chain(Some(&index))
+            .filter_map(|&i| self.entry_word(i))
+            .collect::<Vec<_>>();
+
+        // check every crossing entry still has a fill
+        self.grid.fill_entry(index, word).expect("the length was checked above");
+        let invalid = affected.iter()
+            .cloned()
+            .filter(|&perp| {
+                let pattern = self.grid.entry_pattern(perp).unwrap();
+                let mut fills = self.dict.lookup_iter(&pattern);
+                fills.next().is_none()
+            })
+            .collect::<Vec<_>>();
+        if !invalid.is_empty() {
+            self.grid.set_entry(index, &prev_entry);
+            return Err(invalid);
+        }
+
+        // bring the solver up to date with the new letters
+        for prev_word in &prev_words {
+            self.remove_word(prev_word);
+        }
+        for i in affected.into_iter().chain(Some(index)) {
+            match self.entry_word(i) {
+                Some(new_word) => {
+                    self.add_word(&new_word);
+                    self.unfilled_entries.remove(&i);
+                }
+                None => {
+                    self.unfilled_entries.insert(i);
+                }
             }
-            write!(f, "{}, ", word)?;
+            self.update_possible_fills(i);
         }
-        write!(f, "\n")?;
+        self.changes.clear();
         Ok(())
     }
+
+    // find the entries whose prefilled letters no dictionary word fits, in order
+    // otherwise a hand-entered typo just makes the solve fail with no explanation
+    // empty entries are never flagged
+    pub fn check_prefill(&self) -> Vec<EntryIndex> {
+        self.grid.entry_indices().into_iter()
+            .filter(|&index| !self.is_ignored(index))
+            .filter(|&index| {
+                let entry = self.grid.get_entry(index).unwrap();
+                if entry.letters.iter().all(|letter| letter.is_none()) {
+                    return false;
+                }
+                let pattern = Pattern::new(&entry.letters);
+                let mut fills = self.dict.lookup_iter(&pattern);
+                fills.next().is_none()
+            })
+            .collect()
+    }
+
+    // why the word can't fill the entry right now, or none if it can
+    // the checks run in the order of the variants, so the first problem found is returned
+    // e.g. for showing in a tooltip when a word is dragged onto the grid
+    pub fn explain_rejection(&self, index: EntryIndex, word: &Word) -> Option<Rejection> {
+        let entry = match self.grid.get_entry(index) {
+            Some(ref entry) if entry.letters.len() == word.size() => entry.clone(),
+            _ => return Some(Rejection::LengthMismatch),
+        };
+        if let Some(pos) = Pattern::new(&entry.letters).first_mismatch(word) {
+            return Some(Rejection::PatternConflict(pos));
+        }
+        let id = match self.dict.word_id(word) {
+            Some(id) => id,
+            None => return Some(Rejection::NotInDictionary),
+        };
+        // the word already in this entry doesn't count as a reuse
+        let uses = self.used_words.get(&id).cloned().unwrap_or(0);
+        let own = if self.entry_word(index).as_ref() == Some(word) { 1 } else { 0 };
+        if uses > own {
+            return Some(Rejection::AlreadyUsed);
+        }
+        if self.blocklist.contains(&id) {
+            return Some(Rejection::Blacklisted);
+        }
+
+        // put each new letter into its crossing and make sure some word still fits there
+        let mut perps = self.grid.entries_perp_to(index);
+        perps.sort();
+        perps.dedup();
+        for perp in perps.into_iter().filter(|&perp| !self.is_ignored(perp)) {
+            let pattern = self.crossing_pattern(index, word, perp);
+            let mut fills = self.dict.lookup_ids(&pattern)
+                .filter(|fill| !self.blocklist.contains(fill));
+            if fills.next().is_none() {
+                return Some(Rejection::CrossingDeadEnd(perp));
+            }
+        }
+        None
+    }
+
+    // the letters that could go in the cell, given the letters around it in its entries
+    // and the words in the dictionary
+    // a cell outside every entry the solver fills could hold anything,
+    // and a black cell can't hold any letter
+    pub fn valid_letters_at(&self, coord: GridCoord) -> HashSet<Letter> {
+        let mut valid = Letter::all().collect::<HashSet<_>>();
+        match self.grid.get_cell(coord) {
+            Some(Cell::White(_)) => {}
+            _ => return HashSet::new(),
+        }
+        for index in self.grid.entry_indices() {
+            if self.is_ignored(index) {
+                continue;
+            }
+            let coords = self.grid.get_entry_coords(index).unwrap();
+            let pos = match coords.iter().position(|&c| c == coord) {
+                Some(pos) => pos,
+                None => continue,
+            };
+            let pattern = self.grid.entry_pattern(index).unwrap();
+            let letters = self.dict.lookup_ids(&pattern)
+                .filter(|id| !self.blocklist.contains(id))
+                .map(|id| self.dict.word(id).letters[pos])
+                .collect::<HashSet<_>>();
+            valid.retain(|letter| letters.contains(letter));
+        }
+        valid
+    }
+
+    // the letters that can't go in the cell, i.e. every letter valid_letters_at leaves out
+    // e.g. for greying out eliminated letters in a ui
+    pub fn impossible_letters_at(&self, coord: GridCoord) -> HashSet<Letter> {
+        let valid = self.valid_letters_at(coord);
+        Letter::all()
+            .filter(|letter| !valid.contains(letter))
+            .collect()
+    }
+
+    // how many words each unfilled entry could still take
+    // e.g. for colouring the entries by how hard they'll be to fill, the fewest being the hardest
+    pub fn difficulty_heatmap(&self) -> HashMap<EntryIndex, usize> {
+        self.unfilled_entries.iter()
+            .map(|&index| (index, self.possible_fills[&index].len()))
+            .collect()
+    }
+
+    // find a small set of unfilled entries whose crossings can't all be satisfied
+    // an entry is dropped from the set whenever the rest still can't be,
+    // so no entry in the result can be left out
+    // this is only an approximation: the check is arc consistency, which can miss
+    // some unsatisfiable grids, in which case the result is empty
+    pub fn minimal_conflict_set(&self) -> Vec<EntryIndex> {
+        let mut conflict = self.unfilled_entries.iter().cloned().collect::<Vec<_>>();
+        conflict.sort();
+        if self.arc_consistent(&conflict) {
+            return vec![];
+        }
+        let mut i = 0;
+        while i < conflict.len() {
+            let removed = conflict.remove(i);
+            if self.arc_consistent(&conflict) {
+                conflict.insert(i, removed);
+                i += 1;
+            }
+        }
+        conflict
+    }
+
+    // prune the possible fills of the given entries against each other, only
+    // looking at crossings between them, until nothing changes
+    // returns false if some entry runs out of possible fills
+    fn arc_consistent(&self, entries: &[EntryIndex]) -> bool {
+        let mut domains = entries.iter()
+            .map(|&index| (index, self.possible_fills[&index].clone()))
+            .collect::<HashMap<_, _>>();
+        if domains.values().any(|fills| fills.is_empty()) {
+            return false;
+        }
+
+        // every crossing between two of the entries, along with
+        // where it falls in each entry
+        let mut arcs = vec![];
+        for &index in entries {
+            let coords = self.grid.get_entry_coords(index).unwrap();
+            for perp in self.grid.entries_perp_to(index) {
+                if !domains.contains_key(&perp) {
+                    continue;
+                }
+                let perp_coords = self.grid.get_entry_coords(perp).unwrap();
+                if let Some(pos) = coords.iter().position(|coord| perp_coords.contains(coord)) {
+                    let perp_pos = perp_coords.iter().position(|coord| *coord == coords[pos]).unwrap();
+                    arcs.push((index, pos, perp, perp_pos));
+                }
+            }
+        }
+
+        let mut queue = arcs.clone();
+        while let Some((index, pos, perp, perp_pos)) = queue.pop() {
+            // the letters the crossing entry can still put in the shared cell
+            let supported = domains[&perp].iter()
+                .map(|&id| self.dict.word(id).letters[perp_pos])
+                .collect::<HashSet<_>>();
+            let fills = domains.get_mut(&index).unwrap();
+            let before = fills.len();
+            fills.retain(|&id| supported.contains(&self.dict.word(id).letters[pos]));
+            if fills.is_empty() {
+                return false;
+            }
+            // the entries crossing this one need to be checked again
+            if fills.len() < before {
+                queue.extend(arcs.iter().filter(|arc| arc.2 == index && arc.0 != perp));
+            }
+        }
+        true
+    }
+
+    // find the unfilled entry with the least number of possible fills
+    // ties go to the lowest index, so the same seed always fills the same way
+    fn most_constrained(&self) -> EntryIndex {
+        self.unfilled_entries.iter()
+            .min_by_key(|&&index| (self.possible_fills[&index].len(), index))
+            .cloned()
+            .unwrap()
+    }
+
+    // pick the unfilled entry to fill next according to the variable order
+    fn next_entry(&self) -> EntryIndex {
+        match (self.variable_order, self.ranked) {
+            (VariableOrder::WorstBestScore, Some(ranked)) => {
+                // entries without any possible fills have no best score,
+                // so they come first and the dead end is found right away
+                // ties go to the entry with fewer possible fills, then the lowest index
+                self.unfilled_entries.iter()
+                    .min_by_key(|index| {
+                        let fills = &self.possible_fills[index];
+                        let best = fills.iter()
+                            .filter_map(|&id| (ranked.score)(&self.dict, id))
+                            .max();
+                        (best, fills.len(), **index)
+                    })
+                    .cloned()
+                    .unwrap()
+            }
+            (VariableOrder::ExpectedCost, _) => {
+                // ties go to the entry with fewer possible fills, then the lowest index,
+                // so an entry without any is still found before one whose crossing has none
+                self.unfilled_entries.iter()
+                    .map(|&index| (self.expected_cost(index), self.possible_fills[&index].len(), index))
+                    .min_by(|a, b| a.partial_cmp(b).unwrap())
+                    .map(|(_, _, index)| index)
+                    .unwrap()
+            }
+            _ => self.most_constrained(),
+        }
+    }
+
+    // the number of possible fills of the entry, scaled by the fraction of each unfilled
+    // crossing's possible fills that agree with one of them in the shared cell, on average
+    fn expected_cost(&self, index: EntryIndex) -> f64 {
+        let fills = &self.possible_fills[&index];
+        let coords = self.grid.get_entry_coords(index).unwrap();
+        let mut perps = self.grid.entries_perp_to(index);
+        perps.sort();
+        perps.dedup();
+        perps.retain(|perp| self.unfilled_entries.contains(perp) && !self.is_ignored(*perp));
+        if perps.is_empty() {
+            return fills.len() as f64;
+        }
+        let surviving: f64 = perps.iter()
+            .map(|perp| {
+                let perp_fills = &self.possible_fills[perp];
+                if perp_fills.is_empty() {
+                    return 0f64;
+                }
+                let perp_coords = self.grid.get_entry_coords(*perp).unwrap();
+                let pos = coords.iter().position(|coord| perp_coords.contains(coord)).unwrap();
+                let perp_pos = perp_coords.iter().position(|coord| *coord == coords[pos]).unwrap();
+                // the letters this entry could put in the shared cell
+                let letters = fills.iter()
+                    .map(|&id| self.dict.word(id).letters[pos])
+                    .collect::<HashSet<_>>();
+                let kept = perp_fills.iter()
+                    .filter(|&&id| letters.contains(&self.dict.word(id).letters[perp_pos]))
+                    .count();
+                kept as f64 / perp_fills.len() as f64
+            })
+            .sum();
+        fills.len() as f64 * surviving / perps.len() as f64
+    }
+
+    // the words to try for the given entry, in the order they should be tried
+    fn candidates(&mut self, index: EntryIndex) -> Vec<WordId> {
+        let mut possibilities: Vec<WordId> = self.possible_fills[&index].clone();
+
+        // shuffle the possibile words
+        // they're sorted first so the same seed always gives the same order
+        // they're sorted by word since ids depend on the order words were added to the dictionary
+        // ranked solving keeps the dictionary's order instead
+        if self.ranked.is_none() {
+            let dict = &self.dict;
+            possibilities.sort_by(|&a, &b| dict.word(a).cmp(dict.word(b)));
+            self.rng.shuffle(&mut possibilities);
+        }
+
+        // words far from two vowels in every five letters move down the list,
+        // below any word with a better boosted score
+        if self.vowel_balance {
+            let (dict, boosts) = (&self.dict, &self.boosts);
+            let ranked = self.ranked;
+            possibilities.sort_by_cached_key(|&id| {
+                let word = dict.word(id);
+                let score = ranked.map_or(0, |ranked| {
+                    (ranked.score)(dict, id).unwrap_or(0) + boosts.get(&id).cloned().unwrap_or(0)
+                });
+                (-score, (5 * word.vowel_count()).abs_diff(2 * word.size()))
+            });
+        }
+
+        // penalized words move down the list
+        // the sort is stable, so words with the same score keep their order
+        if !self.soft_constraints.is_empty() {
+            let (dict, boosts, constraints) = (&self.dict, &self.boosts, &self.soft_constraints);
+            let ranked = self.ranked;
+            possibilities.sort_by_cached_key(|&id| {
+                let word = dict.word(id);
+                let score = ranked.map_or(0, |ranked| {
+                    (ranked.score)(dict, id).unwrap_or(0) + boosts.get(&id).cloned().unwrap_or(0)
+                });
+                let penalty: i32 = constraints.iter().map(|constraint| (constraint.0)(word, index)).sum();
+                penalty - score
+            });
+        }
+
+        // required words that aren't in the grid yet go first
+        if !self.required_words.is_empty() {
+            let required = self.required_words.iter()
+                .filter_map(|word| self.dict.word_id(word))
+                .filter(|id| !self.used_words.contains_key(id))
+                .collect::<HashSet<_>>();
+            possibilities.sort_by_key(|id| !required.contains(id));
+        }
+
+        // an entry crossing an obscure word can only take words that aren't obscure
+        if let (Some(threshold), Some(ranked)) = (self.natick_threshold, self.ranked) {
+            if self.crosses_an_obscure_word(index, threshold, ranked) {
+                let dict = &self.dict;
+                possibilities.retain(|&id| (ranked.score)(dict, id).is_some_and(|score| score >= threshold));
+            }
+        }
+
+        // only try a handful of the possible words, if limited
+        if let Some(factor) = self.branch_factor {
+            possibilities.truncate(factor);
+        }
+        self.note_open_candidates(possibilities.len());
+        possibilities
+    }
+
+    // whether any filled entry crossing the given one holds a word scoring under the threshold
+    // a word the dictionary doesn't score, e.g. one prefilled by hand, counts as obscure
+    fn crosses_an_obscure_word(&self, index: EntryIndex, threshold: i32, ranked: RankedLookups<T>) -> bool {
+        self.grid.entries_perp_to(index).into_iter()
+            .filter(|&perp| !self.is_ignored(perp))
+            .filter_map(|perp| self.entry_word(perp))
+            .any(|word| {
+                let score = self.dict.word_id(&word).and_then(|id| (ranked.score)(&self.dict, id));
+                score.is_none_or(|score| score < threshold)
+            })
+    }
+
+    // record that the given entry is about to try another word
+    // returns false if the entry has already used up its attempts
+    // or the solve has used up its steps or time
+    fn attempt(&mut self, index: EntryIndex) -> bool {
+        if let Some(limit) = self.step_limit {
+            if (self.stats.nodes + self.stats.backtracks) as u64 >= limit {
+                self.out_of_steps = true;
+                return false;
+            }
+        }
+        if self.deadline.is_some_and(|deadline| deadline.has_passed()) {
+            self.out_of_steps = true;
+            return false;
+        }
+        let attempts = self.slot_attempts.entry(index).or_insert(0);
+        if let Some(cap) = self.per_slot_cap {
+            if *attempts >= cap {
+                return false;
+            }
+        }
+        *attempts += 1;
+        true
+    }
+
+    // record that the given entry hit a dead end
+    // if it has hit too many, start unwinding to the earliest of its crossings
+    // that the search filled, so that crossing can be re-picked
+    fn note_failure(&mut self, index: EntryIndex) {
+        let (threshold, max_restarts) = match self.local_restarts {
+            Some(limits) if !self.out_of_steps => limits,
+            _ => return,
+        };
+        let failures = self.slot_failures.entry(index).or_insert(0);
+        *failures += 1;
+        if *failures <= threshold || self.restarts_used >= max_restarts {
+            return;
+        }
+        let perps = self.grid.entries_perp_to(index);
+        if let Some(depth) = self.changes.iter().position(|change| perps.contains(&change.0)) {
+            *failures = 0;
+            self.restarts_used += 1;
+            self.restart_depth = Some(depth);
+        }
+    }
+
+    // reset the bookkeeping that only lasts for a single solve
+    fn begin_solve(&mut self) {
+        self.slot_attempts.clear();
+        self.slot_failures.clear();
+        self.restarts_used = 0;
+        self.restart_depth = None;
+        self.stats = SolveStats::default();
+        self.open_candidates.clear();
+        self.note_possible_fills_total();
+        self.solve_started = Instant::now();
+        self.out_of_steps = false;
+        self.dead_end = None;
+    }
+
+    // record that the search frame at the current depth is about to try this many words
+    // every frame is at a different depth, and one at this depth or deeper has finished
+    fn note_open_candidates(&mut self, count: usize) {
+        let depth = self.changes.len();
+        while self.open_candidates.last().is_some_and(|&(open, _)| open >= depth) {
+            self.open_candidates.pop();
+        }
+        self.open_candidates.push((depth, count));
+        self.note_possible_fills_total();
+    }
+
+    // keep track of the most possible fills held at once, counting the words
+    // the open search frames have left to try
+    // filling an entry only shrinks the lists, and undoing one puts them back the way
+    // they were when that frame picked its words, so this is only needed
+    // at the start of a solve and whenever a frame picks its words
+    fn note_possible_fills_total(&mut self) {
+        let open: usize = self.open_candidates.iter().map(|&(_, count)| count).sum();
+        let total = open + self.possible_fills.values().map(|fills| fills.len()).sum::<usize>();
+        self.stats.peak_possible_fills_total = self.stats.peak_possible_fills_total.max(total);
+    }
+
+    // the entry that had no possible fills at the deepest point of the last solve
+    // when a solve fails, this is usually the bottleneck to re-shape the grid around
+    pub fn failure_report(&self) -> Option<EntryIndex> {
+        self.dead_end.map(|(_, index)| index)
+    }
+
+    // pick the next entry to fill, remembering it if it's the deepest dead end so far
+    fn pick_entry(&mut self) -> EntryIndex {
+        let index = self.next_entry();
+        let depth = self.changes.len();
+        let deeper = self.dead_end.is_none_or(|(deepest, _)| depth >= deepest);
+        if deeper && self.possible_fills[&index].is_empty() {
+            self.dead_end = Some((depth, index));
+        }
+        index
+    }
+
+    // fill the grid completely
+    // returns true if it's filled, false otherwise
+    pub fn solve(&mut self) -> bool {
+        self.set_ranked(None);
+        self.begin_solve();
+        self.search()
+    }
+
+    // solve, and say how much work it took
+    // e.g. for comparing how hard two grid designs are to fill
+    pub fn solve_with_stats(&mut self) -> (bool, SolveStats) {
+        let solved = self.solve();
+        (solved, self.stats)
+    }
+
+    // like solve, but if the grid can't be filled, return the grid as it was
+    // at the deepest point the search reached, instead of the unwound one
+    // e.g. for seeing where a grid gets stuck
+    // the solver's own grid is still unwound as usual
+    pub fn solve_best_effort(&mut self) -> (bool, Grid) {
+        self.deepest_fill = Some(self.grid.clone());
+        let solved = self.solve();
+        let deepest = self.deepest_fill.take().unwrap();
+        if solved {
+            (true, self.grid.clone())
+        } else {
+            (false, deepest)
+        }
+    }
+
+    // fill the grid completely, giving up after max_steps fills and undos
+    // when the budget runs out the search unwinds, so the grid is left
+    // as it was before the solve rather than partially filled
+    pub fn solve_with_limit(&mut self, max_steps: u64) -> SolveOutcome {
+        self.set_ranked(None);
+        self.begin_solve();
+        self.step_limit = Some(max_steps);
+        let solved = self.search();
+        self.step_limit = None;
+        if solved {
+            SolveOutcome::Solved
+        } else if self.out_of_steps {
+            SolveOutcome::BudgetExhausted
+        } else {
+            SolveOutcome::Unsolvable
+        }
+    }
+
+    // fill the grid completely, giving up once the deadline passes
+    // returns whether the grid was filled, or none if the search ran out of time,
+    // in which case the grid is left as it was before the solve
+    pub fn solve_until(&mut self, deadline: &Deadline) -> Option<bool> {
+        if deadline.has_passed() {
+            return None;
+        }
+        self.set_ranked(None);
+        self.begin_solve();
+        self.deadline = Some(*deadline);
+        let solved = self.search();
+        self.deadline = None;
+        if !solved && self.out_of_steps {
+            None
+        } else {
+            Some(solved)
+        }
+    }
+
+    // try each config in turn with a budget of per_config_budget fills and undos,
+    // stopping at the first one that fills the grid
+    // different seeds and heuristics get stuck in different places, so for hard grids
+    // several short solves often beat one long one
+    // the variable order and branch factor are put back afterwards, but the seed isn't
+    pub fn solve_portfolio(&mut self, configs: &[SolverConfig], per_config_budget: usize) -> bool {
+        let (order, factor) = (self.variable_order, self.branch_factor);
+        let solved = configs.iter().any(|config| {
+            self.reseed(config.seed);
+            self.variable_order = config.variable_order;
+            self.branch_factor = config.branch_factor;
+            self.solve_with_limit(per_config_budget as u64) == SolveOutcome::Solved
+        });
+        self.variable_order = order;
+        self.branch_factor = factor;
+        solved
+    }
+
+    // clear the cells at the given coordinates and find up to max other ways to fill them
+    // the rest of the grid is kept fixed, so the grids returned only differ
+    // from the current one inside the region
+    // the grid is put back the way it was afterwards, but the undo history is dropped
+    // every word is tried for the region whatever the branch factor, since a small
+    // region with a capped search easily runs out of alternatives
+    pub fn resolve_region_alternatives(&mut self, coords: &[GridCoord], max: usize) -> Vec<Grid> {
+        let original = self.grid.clone();
+        for &coord in coords {
+            if let Some(Cell::White(_)) = self.grid.get_cell(coord) {
+                self.grid.set_cell(coord, Cell::White(None));
+            }
+        }
+        self.sync_with_grid();
+
+        // the current fill is one of the fills found, so look for one more
+        let region_cells = |grid: &Grid| coords.iter().map(|&coord| grid.get_cell(coord)).collect::<Vec<_>>();
+        let current = region_cells(&original);
+        let factor = self.branch_factor.take();
+        let mut alternatives = self.solve_n(max + 1);
+        self.branch_factor = factor;
+        alternatives.retain(|grid| region_cells(grid) != current);
+        alternatives.truncate(max);
+
+        self.grid = original;
+        self.sync_with_grid();
+        alternatives
+    }
+
+    // find up to n different fills of the grid
+    // every fill found is treated like a dead end and undone so the search keeps going,
+    // which means the grid is left as it was before the call
+    // fills differ because they come from different branches of the search
+    pub fn solve_n(&mut self, n: usize) -> Vec<Grid> {
+        self.set_ranked(None);
+        self.begin_solve();
+        let mut solutions = vec![];
+        if n > 0 {
+            let depth = self.changes.len();
+            self.search_accepting(&mut |solver| {
+                solutions.push(solver.grid.clone());
+                solutions.len() >= n
+            });
+            while self.changes.len() > depth {
+                self.undo_last_fill();
+            }
+        }
+        solutions
+    }
+
+    // a search that treats every complete fill the callback rejects as a dead end
+    // returns true once the callback accepts a fill, which is left in the grid
+    fn search_accepting(&mut self, accept: &mut dyn FnMut(&GridSolver<T>) -> bool) -> bool {
+        if self.unfilled_entries.is_empty() {
+            return !self.missing_required_words() && accept(self);
+        }
+
+        let most_constrained = self.pick_entry();
+        for &id in &self.candidates(most_constrained) {
+            if self.wipes_out_a_crossing(most_constrained, id) {
+                continue;
+            }
+            if !self.attempt(most_constrained) {
+                break;
+            }
+            self.fill(most_constrained, id);
+            if self.search_accepting(accept) {
+                return true;
+            }
+            self.undo_last_fill();
+        }
+        false
+    }
+
+    // the recursive backtracking search behind solve and solve_ranked
+    fn search(&mut self) -> bool {
+        // if there are no unfilled entries, we're done,
+        // unless the fill left out a required word
+        if self.unfilled_entries.is_empty() {
+            return !self.missing_required_words();
+        }
+
+        // if there are zero possible fills, the grid cannot be filled
+        // and the loop below never runs
+        let most_constrained = self.pick_entry();
+        'pick: loop {
+            let possibilities = self.candidates(most_constrained);
+
+            // for each word to try, insert that word and recursively try filling the grid
+            for &id in &possibilities {
+                if self.wipes_out_a_crossing(most_constrained, id) {
+                    continue;
+                }
+                if !self.attempt(most_constrained) {
+                    break;
+                }
+                self.fill(most_constrained, id);
+                if self.search() {
+                    return true;
+                }
+                self.undo_last_fill();
+
+                // if a local restart is unwinding the search, keep unwinding
+                // until we're back at the entry it's clearing, which re-picks its words
+                if let Some(depth) = self.restart_depth {
+                    if self.changes.len() > depth {
+                        return false;
+                    }
+                    self.restart_depth = None;
+                    continue 'pick;
+                }
+            }
+            break;
+        }
+
+        // if none of the words work we can't fill the grid
+        self.note_failure(most_constrained);
+        false
+    }
+
+    // fill the grid completely without recursing
+    // this is the same search as solve, but the words left to try for each
+    // entry live on an explicit stack, so huge grids can't overflow the native one
+    // returns true if it's filled, false otherwise
+    pub fn solve_iterative(&mut self) -> bool {
+        self.set_ranked(None);
+        self.begin_solve();
+
+        // every frame holds an entry, the words still left to try for it,
+        // and whether one of its words is currently in the grid
+        let mut stack: Vec<(EntryIndex, Vec<WordId>, bool)> = vec![];
+        loop {
+            // if there are no unfilled entries, we're done,
+            // unless the fill left out a required word, which makes it a dead end
+            if self.unfilled_entries.is_empty() {
+                if !self.missing_required_words() {
+                    return true;
+                }
+            } else {
+                // reverse the words so popping them tries them in the same order as solve
+                let most_constrained = self.pick_entry();
+                let mut possibilities = self.candidates(most_constrained);
+                possibilities.reverse();
+                stack.push((most_constrained, possibilities, false));
+            }
+
+            // insert the next word for the deepest entry, backtracking through
+            // the stack whenever an entry runs out of words
+            loop {
+                match stack.last_mut() {
+                    // we backtracked past the first entry, so the grid can't be filled
+                    None => return false,
+                    Some(frame) => {
+                        if frame.2 {
+                            self.undo_last_fill();
+                            frame.2 = false;
+                        }
+                        while frame.1.last().is_some_and(|&id| self.wipes_out_a_crossing(frame.0, id)) {
+                            frame.1.pop();
+                        }
+                        if !frame.1.is_empty() && self.attempt(frame.0) {
+                            let id = frame.1.pop().unwrap();
+                            self.fill(frame.0, id);
+                            frame.2 = true;
+                            break;
+                        }
+                    }
+                }
+                stack.pop();
+            }
+        }
+    }
+}
+
+impl<T: UnrankedDict> GridSolver<T> {
+    // the dictionary in use, for adding or removing words mid-session
+    // call dictionary_changed afterwards so the solver sees the changes
+    pub fn dict_mut(&mut self) -> &mut T {
+        &mut self.dict
+    }
+}
+
+impl<T: RankedDictLookup> GridSolver<T> {
+    // keep two obscure words, i.e. ones scoring under the threshold, from crossing each other
+    // a solver can't guess the letter where two words it doesn't know cross
+    // a high threshold can leave an entry crossing an obscure prefilled word without any words
+    pub fn set_max_natick_obscurity(&mut self, threshold: i32) {
+        self.natick_threshold = Some(threshold);
+    }
+
+    pub fn average_score(&self) -> f32 {
+        let mut score = 0;
+        for (word, _) in self.grid_words() {
+            score += self.dict.get_score(word).unwrap_or(0);
+        }
+        let words = self.used_words.len() + self.outside_words.len();
+        if words > 0 {
+            (score as f32) / (words as f32)
+        } else {
+            0f32
+        }
+    }
+
+    // the sum of the scores of every word in the grid
+    // a word used twice counts twice
+    pub fn total_score(&self) -> i32 {
+        self.grid_words()
+            .map(|(word, count)| self.dict.get_score(word).unwrap_or(0) * count as i32)
+            .sum()
+    }
+
+    // switch the solver over to ranked solving
+    fn use_ranked(&mut self) {
+        self.set_ranked(Some(RankedLookups {
+            lookup_range: T::lookup_range_ids,
+            score: T::score,
+        }));
+    }
+
+    // fill the grid completely, preferring the dictionary's best words
+    // every entry tries its words from the highest score down
+    // returns true if it's filled, false otherwise
+    pub fn solve_ranked(&mut self) -> bool {
+        self.use_ranked();
+        self.begin_solve();
+        self.search()
+    }
+
+    // like solve_ranked, but only settle for a fill whose average score is at least min_avg
+    // weaker fills are undone and the search keeps looking, for up to
+    // QUALITY_SEARCH_STEPS fills and undos, after which the grid is left as it was
+    pub fn solve_ranked_min_quality(&mut self, min_avg: f32) -> bool {
+        self.use_ranked();
+        self.begin_solve();
+        self.step_limit = Some(QUALITY_SEARCH_STEPS);
+        let solved = self.search_accepting(&mut |solver| solver.average_score() >= min_avg);
+        self.step_limit = None;
+        solved
+    }
+
+    // every fill the ranked search finds that beats the ones before it, with its average score
+    // so each item has a higher score than the last
+    // the search goes on between items, for up to QUALITY_SEARCH_STEPS fills and undos in all
+    // the grid is put back the way it was once the iterator is done or dropped
+    pub fn improving_fills(&mut self) -> impl Iterator<Item = (Grid, f32)> + '_ {
+        self.use_ranked();
+        self.begin_solve();
+        self.step_limit = Some(QUALITY_SEARCH_STEPS);
+        ImprovingFills {
+            depth: self.changes.len(),
+            solver: self,
+            stack: vec![],
+            best: None,
+            finished: false,
+        }
+    }
+}
+
+// ImprovingFills
+// the iterator behind improving_fills
+// it's the search from solve_iterative, paused whenever it finds a better fill
+
+struct ImprovingFills<'a, T: 'a + RankedDictLookup> {
+    solver: &'a mut GridSolver<T>,
+    // the length of the changes stack before the search started
+    depth: usize,
+    // every frame holds an entry, the words still left to try for it,
+    // and whether one of its words is currently in the grid
+    stack: Vec<(EntryIndex, Vec<WordId>, bool)>,
+    // the best average score found so far
+    best: Option<f32>,
+    // whether the whole search has been tried
+    finished: bool,
+}
+
+impl<'a, T: RankedDictLookup> ImprovingFills<'a, T> {
+    // insert the next word for the deepest entry, backtracking through
+    // the stack whenever an entry runs out of words
+    // returns false once the stack is empty
+    fn advance(&mut self) -> bool {
+        let solver = &mut *self.solver;
+        while let Some(frame) = self.stack.last_mut() {
+            if frame.2 {
+                solver.undo_last_fill();
+                frame.2 = false;
+            }
+            while frame.1.last().is_some_and(|&id| solver.wipes_out_a_crossing(frame.0, id)) {
+                frame.1.pop();
+            }
+            if !frame.1.is_empty() && solver.attempt(frame.0) {
+                let id = frame.1.pop().unwrap();
+                solver.fill(frame.0, id);
+                frame.2 = true;
+                return true;
+            }
+            self.stack.pop();
+        }
+        false
+    }
+}
+
+impl<'a, T: RankedDictLookup> Iterator for ImprovingFills<'a, T> {
+    type Item = (Grid, f32);
+
+    fn next(&mut self) -> Option<(Grid, f32)> {
+        while !self.finished {
+            // a complete fill is kept if it's the best yet, and either way
+            // the search moves on as if it were a dead end
+            let mut found = None;
+            if self.solver.unfilled_entries.is_empty() {
+                let score = self.solver.average_score();
+                let better = self.best.is_none_or(|best| score > best);
+                if better && !self.solver.missing_required_words() {
+                    self.best = Some(score);
+                    found = Some((self.solver.grid.clone(), score));
+                }
+            } else {
+                // reverse the words so popping them tries them in order
+                let index = self.solver.pick_entry();
+                let mut possibilities = self.solver.candidates(index);
+                possibilities.reverse();
+                self.stack.push((index, possibilities, false));
+            }
+            self.finished = !self.advance();
+            if found.is_some() {
+                return found;
+            }
+        }
+        None
+    }
+}
+
+impl<'a, T: RankedDictLookup> Drop for ImprovingFills<'a, T> {
+    // put the grid back the way it was, however far the search got
+    fn drop(&mut self) {
+        while self.solver.changes.len() > self.depth {
+            self.solver.undo_last_fill();
+        }
+        self.solver.step_limit = None;
+    }
+}
+
+// how many fills and undos solve_ranked_min_quality and improving_fills
+// may make looking for better fills
+const QUALITY_SEARCH_STEPS: u64 = 100_000;
+
+// the fraction of the grids the dictionary can fill, 0 if there aren't any grids
+// every grid gets up to `attempts` solves, each seeded differently starting from seed
+// this is the number to compare word lists by over a set of grids
+// every solver borrows the dictionary, so it's never copied
+pub fn fill_success_rate<T: DictLookup>(grids: &[Grid], dict: &T, attempts: usize, seed: u64) -> f64 {
+    if grids.is_empty() {
+        return 0f64;
+    }
+    let filled = grids.iter()
+        .filter(|grid| {
+            (0..attempts as u64).any(|attempt| {
+                let mut solver = GridSolver::new((*grid).clone(), dict);
+                solver.reseed(seed.wrapping_add(attempt));
+                solver.solve()
+            })
+        })
+        .count();
+    (filled as f64) / (grids.len() as f64)
+}
+
+// fill every grid in parallel, one grid per worker thread
+// the grid at position i is solved with seed i, so the results are the same
+// as solving them one after another with GridSolver::with_seed
+// every solver borrows the same dictionary, so it's never copied
+// returns each filled grid, or none where a grid couldn't be filled
+#[cfg(feature = "rayon")]
+pub fn solve_many<D: DictLookup + Sync>(grids: Vec<Grid>, dict: &D) -> Vec<Option<Grid>> {
+    use rayon::prelude::*;
+
+    grids.into_par_iter()
+        .enumerate()
+        .map(|(i, grid)| {
+            let mut solver = GridSolver::with_seed(grid, dict, i as u64);
+            if solver.solve() {
+                Some(solver.grid)
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+// count one less of the key, dropping it once there are none
+fn forget_one<K: Eq + Hash>(counts: &mut HashMap<K, usize>, key: &K) {
+    let gone = match counts.get_mut(key) {
+        Some(count) => {
+            *count -= 1;
+            *count == 0
+        }
+        None => false,
+    };
+    if gone {
+        counts.remove(key);
+    }
+}
+
+// a random number generator seeded from the given number
+fn seeded_rng(seed: u64) -> StdRng {
+    StdRng::from_seed(&[seed as usize, (seed >> 32) as usize][..])
+}
+
+impl<T: DictLookup> fmt::Display for GridSolver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.grid)?;
+        let mut added_words = self.grid_words().map(|(word, _)| word).collect::<Vec<_>>();
+        if added_words.is_empty() {
+            return writeln!(f, "no words added yet");
+        } else {
+            writeln!(f, "number of words: {}", added_words.len())?;
+        }
+        added_words.sort_by_key(|word| word.size());
+        let mut prev_word_size = added_words[0].size();
+        for word in &added_words {
+            if prev_word_size < word.size() {
+                writeln!(f)?;
+                prev_word_size = word.size();
+            }
+            write!(f, "{}, ", word)?;
+        }
+        writeln!(f)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use dict::{Dictionary, RankedDict, RankedDictionary};
+
+    fn grid_from_rows(rows: &[&str]) -> Grid {
+        Grid::from_rows(rows).unwrap()
+    }
+
+    fn dict_from(words: &[&str]) -> Dictionary {
+        let mut dict = Dictionary::new();
+        for word in words {
+            dict.add(&Word::from(*word));
+        }
+        dict
+    }
+
+    fn ranked_dict_from(words: &[(&str, i32)]) -> RankedDictionary {
+        let mut dict = RankedDictionary::new();
+        for &(word, score) in words {
+            let word = Word::from(word);
+            dict.add(&word);
+            dict.set_score(&word, score);
+        }
+        dict
+    }
+
+    // every entry in the grid is filled with a word from the dictionary
+    fn is_valid_fill<T: DictLookup>(grid: &Grid, dict: &T) -> bool {
+        grid.is_filled() && grid.entries().iter().all(|entry| {
+            let letters: Vec<Letter> = entry.letters.iter().flatten().cloned().collect();
+            dict.contains(&Word::new(&letters))
+        })
+    }
+
+    // writes the contents to a file in the temp directory and returns its path
+    fn temp_file(name: &str, contents: &str) -> ::std::path::PathBuf {
+        let path = ::std::env::temp_dir().join(format!("gridsolver_{}_{}", ::std::process::id(), name));
+        File::create(&path).unwrap().write_all(contents.as_bytes()).unwrap();
+        path
+    }
+
+    #[test]
+    fn from_file_loads_the_assets() {
+        let grid = Grid::from_file("./assets/grid1.txt").unwrap();
+        assert_eq!((grid.width(), grid.height()), (4, 5));
+        for path in &["./assets/grid2.txt", "./assets/grid3.txt", "./assets/grid4.txt"] {
+            let grid = Grid::from_file(path).unwrap();
+            assert_eq!((grid.width(), grid.height()), (15, 15));
+        }
+    }
+
+    #[test]
+    fn from_file_rejects_malformed_headers() {
+        for &(name, contents) in &[("empty", ""), ("one", "5\n....\n"), ("abc", "5,abc\n....\n")] {
+            let err = Grid::from_file(temp_file(name, contents)).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+        // too few cells for the header
+        let err = Grid::from_file(temp_file("short", "2, 2\n..\n")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn from_file_delimited_skips_separators() {
+        let path = temp_file("delimited.txt", "3, 4\nC|A|T|#\n-+-+-+-\n.|#|.|.\n-+-+-+-\nD|O|G|.\n");
+        let grid = Grid::from_file_delimited(&path, &['|', '-', '+']).unwrap();
+        assert_eq!((grid.height(), grid.width()), (3, 4));
+        assert_eq!(grid.get_cell(GridCoord::new(0, 1)), Some(Cell::White(Letter::try_from(b'A').ok())));
+        assert_eq!(grid.get_cell(GridCoord::new(0, 3)), Some(Cell::Black));
+        assert_eq!(grid.get_cell(GridCoord::new(1, 0)), Some(Cell::White(None)));
+        assert_eq!(grid.get_cell(GridCoord::new(1, 1)), Some(Cell::Black));
+        assert_eq!(grid.get_cell(GridCoord::new(2, 2)), Some(Cell::White(Letter::try_from(b'G').ok())));
+        assert_eq!(grid.get_cell(GridCoord::new(2, 3)), Some(Cell::White(None)));
+
+        // a row that's too short is reported rather than shifting the rest
+        let path = temp_file("delimited_short.txt", "2, 3\nC|A\nD|O|G\n");
+        assert_eq!(Grid::from_file_delimited(&path, &['|']).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn is_valid_finds_orphaned_cells() {
+        assert_eq!(Grid::from_file("./assets/grid1.txt").unwrap().is_valid(), Ok(()));
+        assert_eq!(grid_from_rows(&["...", ".#.", "..."]).is_valid(), Ok(()));
+
+        let grid = grid_from_rows(&["...", "###", "#.#"]);
+        assert_eq!(grid.is_valid(), Err(vec![GridCoord::new(2, 1)]));
+    }
+
+    #[test]
+    fn symmetric_entry_pairs_mirror_each_other() {
+        let grid = grid_from_rows(&["...#", "....", "....", "#..."]);
+        let pairs = grid.symmetric_entry_pairs();
+        // two across pairs and two down pairs, since no entry crosses the centre
+        assert_eq!(pairs.len(), 4);
+        for (a, b) in pairs {
+            let a_coords = grid.get_entry_coords(a).unwrap();
+            let mut b_coords = grid.get_entry_coords(b).unwrap();
+            assert_eq!(a_coords.len(), b_coords.len());
+            b_coords.reverse();
+            for (a_coord, b_coord) in a_coords.iter().zip(b_coords) {
+                assert_eq!(a_coord.row + b_coord.row, 3);
+                assert_eq!(a_coord.col + b_coord.col, 3);
+            }
+        }
+
+        // the short bottom entry leaves the top one with nothing to pair with
+        let grid = grid_from_rows(&["....", "#..#", "...#"]);
+        let down = |num| EntryIndex::try_from((num, EntryDir::Down)).unwrap();
+        assert_eq!(grid.symmetric_entry_pairs(), vec![(down(2), down(3))]);
+    }
+
+    #[test]
+    fn symmetry_checks_and_symmetrize() {
+        // the only black cell is in the top left corner
+        let mut grid = Grid::from_file("./assets/grid1.txt").unwrap();
+        assert!(!grid.is_rotationally_symmetric());
+        assert!(!grid.is_symmetric(SymmetryKind::LeftRight));
+        grid.symmetrize();
+        assert!(grid.is_rotationally_symmetric());
+        assert_eq!(grid.get_cell(GridCoord::new(4, 3)), Some(Cell::Black));
+        // the bottom row and last column both lost a cell
+        assert_eq!(grid.average_entry_len(), 36.0 / 9.0);
+
+        let grid = grid_from_rows(&["#..#", "....", "...."]);
+        assert!(!grid.is_rotationally_symmetric());
+        assert!(grid.is_symmetric(SymmetryKind::LeftRight));
+        assert!(!grid.is_symmetric(SymmetryKind::TopBottom));
+
+        let grid = grid_from_rows(&["#...", "....", "#..."]);
+        assert!(grid.is_symmetric(SymmetryKind::TopBottom));
+        assert!(grid_from_rows(&["...", "...", "..."]).is_rotationally_symmetric());
+    }
+
+    #[test]
+    fn cap_word_length_breaks_long_entries_symmetrically() {
+        let rows = vec!["........."; 9];
+        for seed in 0..3 {
+            let mut grid = grid_from_rows(&rows);
+            grid.cap_word_length(5, seed);
+            assert!(grid.is_rotationally_symmetric());
+            assert!(grid.entry_indices().iter().all(|&index| grid.get_entry_coords(index).unwrap().len() <= 5));
+            // splitting a nine letter run evenly never needs a two letter run
+            assert_eq!(grid.short_run_count(), 0);
+        }
+
+        // entries already short enough are left alone
+        let mut grid = Grid::from_file("./assets/grid1.txt").unwrap();
+        let before = grid.cells.clone();
+        grid.cap_word_length(5, 0);
+        assert_eq!(grid.cells, before);
+    }
+
+    #[test]
+    fn black_cluster_sizes_use_orthogonal_neighbors() {
+        let grid = grid_from_rows(&["##...#", "##....", ".....#", "#.#..."]);
+        // the 2x2 block, the two right edge cells on their own, and the two diagonal ones
+        assert_eq!(grid.black_cluster_sizes(), vec![4, 1, 1, 1, 1]);
+        assert!(grid_from_rows(&["...", "..."]).black_cluster_sizes().is_empty());
+        assert_eq!(grid_from_rows(&["#.#", "###"]).black_cluster_sizes(), vec![5]);
+    }
+
+    #[test]
+    fn grid_stats() {
+        let grid = grid_from_rows(&["#...", "....", "...#"]);
+        assert_eq!(grid.black_cell_ratio(), 2.0 / 12.0);
+        // three across entries of 3, 4 and 3, and two down entries of 3
+        assert_eq!(grid.average_entry_len(), 16.0 / 5.0);
+        assert_eq!(grid_from_rows(&["#.#"]).average_entry_len(), 0.0);
+    }
+
+    #[test]
+    fn display_shows_the_average_length() {
+        // five across entries of 3, 4, 4, 4 and 4, and four down entries of 4, 5, 5 and 5
+        let grid = Grid::from_file("./assets/grid1.txt").unwrap();
+        assert!(grid.to_string().starts_with("5x4, 9 words, 4.2 average length\n"));
+        assert!(grid_from_rows(&["#.#"]).to_string().starts_with("1x3, 0 words, 0.0 average length\n"));
+    }
+
+    #[test]
+    fn min_entry_len_changes_the_entries() {
+        let grid = Grid::with_min_entry_len(4, 1, 2).unwrap();
+        assert_eq!(grid.entry_indices().len(), 1);
+
+        let mut grid = grid_from_rows(&["..#.."]);
+        assert_eq!(grid.entry_indices().len(), 0);
+        grid.set_min_entry_len(2);
+        assert_eq!(grid.entry_indices().len(), 2);
+        grid.set_min_entry_len(1);
+        // every white cell is now also a one letter down entry
+        assert_eq!(grid.entry_indices().len(), 6);
+    }
+
+    #[test]
+    fn from_rows_infers_dimensions() {
+        let grid = Grid::from_rows(&["#..P", "...L", "REGI"]).unwrap();
+        assert_eq!((grid.width(), grid.height()), (4, 3));
+        assert_eq!(grid.get_cell((0, 0).into()), Some(Cell::Black));
+        assert_eq!(grid.get_cell((0, 1).into()), Some(Cell::White(None)));
+        assert_eq!(grid.get_cell((1, 3).into()), Some(Cell::White(Letter::try_from(b'L').ok())));
+        assert_eq!(grid.get_cell((2, 0).into()), Some(Cell::White(Letter::try_from(b'R').ok())));
+
+        assert!(Grid::from_rows(&["...", ".."]).is_none());
+        assert!(Grid::from_rows(&[]).is_none());
+    }
+
+    #[test]
+    fn to_ipuz_is_valid_json() {
+        let grid = grid_from_rows(&["#..P", "...L", "...A", "REGI", "TREN"]);
+        let ipuz: ::serde_json::Value = ::serde_json::from_str(&grid.to_ipuz()).unwrap();
+        assert_eq!(ipuz["dimensions"]["width"], 4);
+        assert_eq!(ipuz["dimensions"]["height"], 5);
+        let (puzzle, solution) = (ipuz["puzzle"].as_array().unwrap(), ipuz["solution"].as_array().unwrap());
+        assert_eq!(puzzle.len(), 5);
+        assert!(puzzle.iter().chain(solution).all(|row| row.as_array().unwrap().len() == 4));
+        assert_eq!(puzzle[0], ::serde_json::json!(["#", 1, 2, 3]));
+        assert_eq!(puzzle[1][0], 4);
+        assert_eq!(puzzle[1][1], 0);
+        assert_eq!(solution[0], ::serde_json::json!(["#", null, null, "P"]));
+        assert_eq!(solution[3], ::serde_json::json!(["R", "E", "G", "I"]));
+        let clues = ipuz["clues"]["Across"].as_array().unwrap().len() + ipuz["clues"]["Down"].as_array().unwrap().len();
+        assert_eq!(clues, grid.entry_indices().len());
+    }
+
+    #[test]
+    fn to_jpz_has_a_cell_per_grid_cell() {
+        let grid = grid_from_rows(&["#..P", "...L", "...A", "REGI"]);
+        let jpz = grid.to_jpz();
+        assert!(jpz.contains("<grid width=\"4\" height=\"4\">"));
+        assert_eq!(jpz.matches("<cell ").count(), 16);
+        assert_eq!(jpz.matches("type=\"block\"").count(), 1);
+        assert_eq!(jpz.matches("solution=").count(), 7);
+        assert!(jpz.contains("<cell x=\"1\" y=\"1\" type=\"block\"></cell>"));
+        assert!(jpz.contains("<cell x=\"2\" y=\"1\" number=\"1\"></cell>"));
+        assert!(jpz.contains("<cell x=\"4\" y=\"1\" solution=\"P\" number=\"3\"></cell>"));
+        assert!(jpz.contains("<cell x=\"2\" y=\"4\" solution=\"E\"></cell>"));
+        assert_eq!(jpz.matches("<clue ").count(), grid.entry_indices().len());
+    }
+
+    #[test]
+    fn replace_word_rolls_back_broken_crossings() {
+        // the C makes CAT the only word for the across entry
+        let grid = grid_from_rows(&["C..", "#.#", "#.#"]);
+        let dict = dict_from(&["CAT", "ARE", "DOG", "BAT"]);
+        let mut solver = GridSolver::new(grid, dict);
+        assert!(solver.solve());
+
+        let across = EntryIndex::default();
+        let down = EntryIndex::try_from((2, EntryDir::Down)).unwrap();
+        assert_eq!(solver.entry_word(across), Some(Word::from("CAT")));
+
+        // DOG would leave the down entry starting with O
+        assert_eq!(solver.replace_word(across, &Word::from("DOG")), Err(vec![down]));
+        assert_eq!(solver.entry_word(across), Some(Word::from("CAT")));
+        assert_eq!(solver.entry_word(down), Some(Word::from("ARE")));
+
+        // BAT keeps the A, so the crossing is fine
+        assert_eq!(solver.replace_word(across, &Word::from("BAT")), Ok(()));
+        assert_eq!(solver.entry_word(across), Some(Word::from("BAT")));
+        assert!(solver.added_words().contains_key(&Word::from("BAT")));
+        assert!(!solver.added_words().contains_key(&Word::from("CAT")));
+
+        // the fills from the solve can't be undone any more, since the grid has changed under them
+        assert!(solver.changes.is_empty());
+        solver.undo_last_fill();
+        assert_eq!(solver.entry_word(across), Some(Word::from("BAT")));
+        assert_eq!(solver.entry_word(down), Some(Word::from("ARE")));
+
+        // words have to come from the dictionary, and can't be blocked
+        assert_eq!(solver.replace_word(across, &Word::from("HAT")), Err(vec![across]));
+        solver.add_blocked(&Word::from("BAT"));
+        assert_eq!(solver.replace_word(across, &Word::from("BAT")), Err(vec![across]));
+        assert_eq!(solver.entry_word(across), Some(Word::from("BAT")));
+    }
+
+    #[test]
+    fn puz_round_trips() {
+        let grid = grid_from_rows(&["CAT#", "O..#", "T...", "##.."]);
+        let path = temp_file("round_trip.puz", "");
+        grid.to_puz(&path).unwrap();
+        let loaded = Grid::from_puz(&path).unwrap();
+        assert_eq!(loaded.width(), 4);
+        assert_eq!(loaded.height(), 4);
+        assert_eq!(loaded.cells, grid.cells);
+
+        // the header checksum covers the dimensions and clue count,
+        // and the two letter runs at the bottom right get clues too
+        let mut bytes = vec![];
+        File::open(&path).unwrap().read_to_end(&mut bytes).unwrap();
+        assert_eq!(&bytes[0x02..0x0E], PUZ_MAGIC);
+        assert_eq!(bytes[0x0E..0x10], u16_le(puz_checksum(&bytes[0x2C..0x34], 0)));
+        assert_eq!(bytes[0x2E], 8);
+    }
+
+    #[test]
+    fn fill_entry_rejects_words_that_dont_fit() {
+        let mut grid = grid_from_rows(&["...#", "....", "#..."]);
+        let across = EntryIndex::try_from((5, EntryDir::Across)).unwrap();
+        let before = grid.cells.clone();
+        assert_eq!(grid.fill_entry(across, &Word::from("CATS")), Err(FillError::LengthMismatch { entry: 3, word: 4 }));
+        assert_eq!(grid.fill_entry(across, &Word::from("CA")), Err(FillError::LengthMismatch { entry: 3, word: 2 }));
+        let missing = EntryIndex::try_from((9, EntryDir::Down)).unwrap();
+        assert_eq!(grid.fill_entry(missing, &Word::from("CAT")), Err(FillError::NoSuchEntry));
+        assert_eq!(grid.cells, before);
+        assert_eq!(grid.fill_entry(across, &Word::from("CAT")), Ok(()));
+    }
+
+    #[test]
+    fn entry_pattern_shows_the_known_letters() {
+        let grid = grid_from_rows(&["C.T#", "....", "#..E"]);
+        assert_eq!(grid.entry_pattern(EntryIndex::default()).unwrap().to_string(), "C.T");
+        let down = EntryIndex::try_from((3, EntryDir::Down)).unwrap();
+        assert_eq!(grid.entry_pattern(down).unwrap().to_string(), "T..");
+        let missing = EntryIndex::try_from((9, EntryDir::Across)).unwrap();
+        assert_eq!(grid.entry_pattern(missing), None);
+    }
+
+    #[test]
+    fn entry_letters_follow_reading_order() {
+        let mut grid = grid_from_rows(&["...#", "....", "#..."]);
+        let across = EntryIndex::try_from((5, EntryDir::Across)).unwrap();
+        let down = EntryIndex::try_from((2, EntryDir::Down)).unwrap();
+        assert_eq!(grid.get_entry_coords(across).unwrap(),
+                   vec![GridCoord::new(2, 1), GridCoord::new(2, 2), GridCoord::new(2, 3)]);
+        assert_eq!(grid.get_entry_coords(down).unwrap(),
+                   vec![GridCoord::new(0, 1), GridCoord::new(1, 1), GridCoord::new(2, 1)]);
+
+        // the first letter lands in the first cell, and so on
+        grid.fill_entry(across, &Word::from("CAT")).unwrap();
+        grid.fill_entry(down, &Word::from("ORC")).unwrap();
+        for (coord, letter) in &[((2, 1), b'C'), ((2, 2), b'A'), ((2, 3), b'T'), ((0, 1), b'O'), ((1, 1), b'R')] {
+            assert_eq!(grid.get_cell(GridCoord::from(*coord)), Some(Cell::White(Some(Letter::try_from(*letter).unwrap()))));
+        }
+        assert_eq!(Pattern::new(&grid.get_entry(across).unwrap().letters), Pattern::from("CAT"));
+        assert_eq!(Pattern::new(&grid.get_entry(down).unwrap().letters), Pattern::from("ORC"));
+
+        // set_entry reads back the same way get_entry wrote it
+        let entry = grid.get_entry(down).unwrap();
+        grid.clear_entry(down);
+        grid.set_entry(down, &entry);
+        assert_eq!(Pattern::new(&grid.get_entry(down).unwrap().letters), Pattern::from("ORC"));
+    }
+
+    #[test]
+    fn entry_indices_are_sorted() {
+        let grid = Grid::from_file("./assets/grid1.txt").unwrap();
+        let indices = grid.entry_indices();
+        assert_eq!(indices, grid.entry_indices());
+        assert!(indices.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(indices[0], EntryIndex::default());
+        let entries = indices.iter().map(|&index| grid.get_entry(index).unwrap()).collect::<Vec<_>>();
+        assert_eq!(grid.entries(), entries);
+    }
+
+    #[test]
+    fn iter_entries_lists_every_entry_in_order() {
+        let grid = Grid::from_file("./assets/grid1.txt").unwrap();
+        let entries = grid.iter_entries().collect::<Vec<_>>();
+        let (first, ref entry, coords) = entries[0];
+        assert_eq!(first.to_string(), "1 across");
+        assert_eq!(coords.len(), 3);
+        assert_eq!(entry.letters.len(), 3);
+        assert_eq!(coords[0], GridCoord::new(0, 1));
+
+        assert_eq!(entries.len(), grid.entry_indices().len());
+        assert!(entries.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        for (index, entry, coords) in entries {
+            assert_eq!(Some(entry), grid.get_entry(index));
+            assert_eq!(Some(coords.to_vec()), grid.get_entry_coords(index));
+        }
+    }
+
+    #[test]
+    fn dependency_layers_start_at_the_top_left() {
+        let grid = grid_from_rows(&["...", "...", "..."]);
+        let index = |num, dir| EntryIndex::try_from((num, dir)).unwrap();
+        let layers = grid.entry_dependency_layers();
+        // 1 across comes first, then each down crosses only the across entries before it
+        // and each later across crosses all three downs
+        assert_eq!(layers, vec![vec![index(1, EntryDir::Across)],
+                                vec![index(1, EntryDir::Down), index(2, EntryDir::Down), index(3, EntryDir::Down)],
+                                vec![],
+                                vec![index(4, EntryDir::Across), index(5, EntryDir::Across)]]);
+
+        let grid = Grid::from_file("./assets/grid1.txt").unwrap();
+        let layers = grid.entry_dependency_layers();
+        assert!(layers[0].contains(&EntryIndex::default()));
+        assert_eq!(layers.iter().map(|layer| layer.len()).sum::<usize>(), grid.entry_indices().len());
+    }
+
+    #[test]
+    fn rotate_90_turns_the_grid_clockwise() {
+        let grid = grid_from_rows(&["CAT#", "O..#", "#DOG"]);
+        let rotated = grid.rotate_90();
+        assert_eq!((rotated.height(), rotated.width()), (4, 3));
+        assert_eq!(rotated.cells, grid_from_rows(&["#OC", "D.A", "O.T", "G##"]).cells);
+        // CAT went from across to down
+        let down = EntryIndex::try_from((2, EntryDir::Down)).unwrap();
+        assert_eq!(rotated.get_entry(down), grid.get_entry(EntryIndex::default()));
+
+        let mut turned = grid.clone();
+        for turns in 1..5 {
+            turned = turned.rotate_90();
+            let expected = if turns % 2 == 1 { (4, 3) } else { (3, 4) };
+            assert_eq!((turned.height(), turned.width()), expected);
+        }
+        assert_eq!(turned.cells, grid.cells);
+        assert_eq!(turned.entry_indices(), grid.entry_indices());
+    }
+
+    #[test]
+    fn clue_numbers_follow_reading_order() {
+        let grid = Grid::from_file("./assets/grid1.txt").unwrap();
+        let numbers = grid.clue_numbers();
+        let expected = [((0, 1), 1), ((0, 2), 2), ((0, 3), 3), ((1, 0), 4), ((2, 0), 5), ((3, 0), 6), ((4, 0), 7)]
+            .iter()
+            .map(|&(coord, num)| (GridCoord::from(coord), num))
+            .collect::<HashMap<_, _>>();
+        assert_eq!(numbers, expected);
+
+        // 1 and 4 start both an across and a down entry
+        for &num in &[1, 4] {
+            let across = EntryIndex::try_from((num, EntryDir::Across)).unwrap();
+            let down = EntryIndex::try_from((num, EntryDir::Down)).unwrap();
+            assert_eq!(grid.get_entry_coords(across).unwrap()[0], grid.get_entry_coords(down).unwrap()[0]);
+        }
+    }
+
+    #[test]
+    fn entries_at_finds_the_crossing_entries() {
+        let grid = grid_from_rows(&["CAT#", "O..#", "#DOG"]);
+        let across = |number| EntryIndex::try_from((number, EntryDir::Across)).unwrap();
+        let down = |number| EntryIndex::try_from((number, EntryDir::Down)).unwrap();
+        assert_eq!(grid.entries_at(GridCoord::new(0, 1)), vec![across(1), down(2)]);
+        assert_eq!(grid.entries_at(GridCoord::new(1, 0)), vec![across(4)]);
+        assert!(grid.entries_at(GridCoord::new(0, 3)).is_empty());
+        assert!(grid.entries_at(GridCoord::new(3, 0)).is_empty());
+        assert!(grid.entries_at(GridCoord::new(0, 4)).is_empty());
+    }
+
+    #[test]
+    fn pad_surrounds_the_grid() {
+        let grid = grid_from_rows(&["CAT", "O.#", "W.."]);
+        let padded = grid.pad(1, 2, 0, 1, Cell::Black);
+        assert_eq!((padded.height(), padded.width()), (6, 4));
+        assert_eq!(padded.cells, grid_from_rows(&["####", "CAT#", "O.##", "W..#", "####", "####"]).cells);
+        for row in 0..3 {
+            for col in 0..3 {
+                assert_eq!(padded.get_cell(GridCoord::new(row + 1, col)), grid.get_cell(GridCoord::new(row, col)));
+            }
+        }
+        // black padding doesn't change the entries, just their numbers
+        assert_eq!(padded.entry_indices().len(), grid.entry_indices().len());
+
+        // white padding can make new ones
+        let padded = grid.pad(0, 0, 0, 1, Cell::White(None));
+        assert_eq!(padded.get_entry(EntryIndex::default()).unwrap().letters.len(), 4);
+    }
+
+    #[test]
+    fn clear_all_keeps_the_black_cells() {
+        let mut grid = grid_from_rows(&["CAT#", "O..#", "#DOG"]);
+        let entries = grid.entry_indices();
+        grid.clear_all();
+        assert_eq!(grid.cells, grid_from_rows(&["...#", "...#", "#..."]).cells);
+        assert_eq!(grid.entry_indices(), entries);
+    }
+
+    #[test]
+    fn solvers_can_share_a_dictionary() {
+        let dict = Rc::new(dict_from(&["CAT", "ARE", "TEA", "AXE", "RAT", "EAR"]));
+        let mut first = GridSolver::new(grid_from_rows(&["C..", "...", "..."]), Rc::clone(&dict));
+        let mut second = GridSolver::new(grid_from_rows(&["...", "###", "..."]), Rc::clone(&dict));
+        assert!(first.solve());
+        assert!(second.solve());
+        assert_eq!(Rc::strong_count(&dict), 3);
+
+        // changing one solver's dictionary leaves the others alone
+        second.dict_mut().remove(&Word::from("CAT"));
+        assert!(!second.dict().contains(&Word::from("CAT")));
+        assert!(first.dict().contains(&Word::from("CAT")));
+        assert_eq!(Rc::strong_count(&dict), 2);
+
+        // ranked dictionaries can be shared too
+        let ranked = Rc::new(ranked_dict_from(&[("CAT", 90), ("DOG", 50)]));
+        let mut solver = GridSolver::new(grid_from_rows(&["..."]), Rc::clone(&ranked));
+        assert!(solver.solve_ranked());
+        assert_eq!(solver.total_score(), 90);
+
+        // or just borrowed
+        let mut first = GridSolver::new(grid_from_rows(&["..."]), &*ranked);
+        let mut second = GridSolver::new(grid_from_rows(&["C..", "...", "..."]), &*dict);
+        assert!(first.solve_ranked());
+        assert!(second.solve());
+        assert_eq!(first.total_score(), 90);
+    }
+
+    #[test]
+    fn new_grid_starts_over_with_the_same_dictionary() {
+        let dict = dict_from(&["CAT", "ARE", "TEA", "AXE", "RAT", "EAR"]);
+        let mut solver = GridSolver::new(grid_from_rows(&["Q..", "...", "..."]), dict);
+        assert!(!solver.solve());
+
+        let grid = grid_from_rows(&["C..", "...", "..."]);
+        solver.new_grid(grid.clone());
+        assert_eq!(solver.grid().cells, grid.cells);
+        assert_eq!(solver.unfilled_entries.len(), 6);
+        assert!(solver.possible_fills.values().all(|fills| !fills.is_empty()));
+        assert!(solver.solve());
+    }
+
+    #[test]
+    fn reset_undoes_everything_but_the_prefill() {
+        let grid = grid_from_rows(&["C..", "...", "..."]);
+        let mut solver = GridSolver::new(grid.clone(), dict_from(&["CAT", "ARE", "TEA", "AXE", "RAT", "EAR"]));
+        let fills = solver.possible_fills.clone();
+        assert!(solver.solve());
+        solver.reset();
+        assert_eq!(solver.grid().cells, grid.cells);
+        assert!(solver.added_words().is_empty());
+        assert!(solver.changes.is_empty());
+        assert_eq!(solver.unfilled_entries.len(), grid.entry_indices().len());
+        assert_eq!(solver.possible_fills, fills);
+
+        // and it can be solved again
+        assert!(solver.solve());
+    }
+
+    #[test]
+    fn signatures_only_depend_on_the_layout() {
+        let empty = grid_from_rows(&["...#", "....", "#..."]);
+        let filled = grid_from_rows(&["CAT#", "O...", "#DOG"]);
+        assert_eq!(empty.signature(), filled.signature());
+        assert_eq!(empty.signature(), empty.clone().signature());
+        assert_ne!(empty.signature(), grid_from_rows(&["#...", "....", "...#"]).signature());
+        // the same cells in a different shape
+        assert_ne!(grid_from_rows(&["......"]).signature(), grid_from_rows(&["...", "..."]).signature());
+    }
+
+    #[test]
+    fn tokens_round_trip() {
+        let mut solver = GridSolver::new(grid_from_rows(&["CAT#", "....", "#..."]), dict_from(&["CAT"]));
+        let token = solver.to_token().unwrap();
+        assert!(token.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_'));
+        let grid = Grid::from_token(&token).unwrap();
+        assert_eq!((grid.height(), grid.width()), (3, 4));
+        assert_eq!(grid.cells, solver.grid().cells);
+
+        // every length of leftover bytes encodes and decodes
+        for rows in &[&["."][..], &[".."], &["..."], &["#A", "B."]] {
+            let grid = grid_from_rows(rows);
+            assert_eq!(Grid::from_token(&grid.to_token().unwrap()).unwrap().cells, grid.cells);
+        }
+
+        assert!(Grid::from_token("not a token!").is_none());
+        assert!(Grid::from_token("").is_none());
+        solver.grid.set_cell(GridCoord::new(1, 1), Cell::Black);
+        let mut token = solver.to_token().unwrap();
+        token.pop();
+        assert!(Grid::from_token(&token).is_none());
+
+        // the size has to fit in two bytes
+        assert!(Grid::new(u16::MAX as usize, 1).unwrap().to_token().is_some());
+        assert!(Grid::new(u16::MAX as usize + 1, 1).unwrap().to_token().is_none());
+    }
+
+    #[test]
+    fn from_puz_rejects_other_files() {
+        let path = temp_file("not_a_puzzle.puz", "3, 3\n...\n...\n...\n");
+        assert_eq!(Grid::from_puz(&path).unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        // a header claiming a bigger grid than the file holds
+        let grid = grid_from_rows(&["...", "...", "..."]);
+        let mut bytes = grid.puz_bytes().unwrap();
+        bytes[0x2C] = 20;
+        let path = temp_file("truncated.puz", "");
+        File::create(&path).unwrap().write_all(&bytes).unwrap();
+        assert_eq!(Grid::from_puz(&path).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn words_outside_the_dictionary_are_still_counted() {
+        // CAT is prefilled before it's in the dictionary
+        let grid = grid_from_rows(&["CAT", "###", "..."]);
+        let mut solver = GridSolver::new(grid, dict_from(&["DOG"]));
+        let (cat, bottom) = (Word::from("CAT"), EntryIndex::try_from((2, EntryDir::Across)).unwrap());
+        assert_eq!(solver.added_words().get(&cat), Some(&1));
+
+        // once it's added it counts as a dictionary word, so it can't be used again
+        solver.dict_mut().add(&cat);
+        solver.dictionary_changed();
+        assert_eq!(solver.added_words().get(&cat), Some(&1));
+        assert_eq!(solver.explain_rejection(bottom, &cat), Some(Rejection::AlreadyUsed));
+        assert_eq!(solver.explain_rejection(bottom, &Word::from("DOG")), None);
+    }
+
+    #[test]
+    fn dictionary_changed_refreshes_possible_fills() {
+        let grid = grid_from_rows(&["C..", "###", "..."]);
+        let mut solver = GridSolver::new(grid, dict_from(&["CAT", "DOG"]));
+        let across = EntryIndex::default();
+        let new_word = Word::from("COT");
+
+        solver.dict_mut().add(&new_word);
+        let id = solver.dict().word_id(&new_word).unwrap();
+        assert!(!solver.possible_fills[&across].contains(&id));
+        solver.dictionary_changed();
+        assert!(solver.possible_fills[&across].contains(&id));
+        assert!(solver.candidates(across).contains(&id));
+    }
+
+    #[test]
+    fn across_only_solving_ignores_down_entries() {
+        let rows = ["...", "...", "..."];
+        let dict = dict_from(&["CAT", "DOG"]);
+        // no fill has words going down
+        let mut solver = GridSolver::new(grid_from_rows(&rows), dict.clone());
+        assert!(!solver.solve());
+
+        let mut solver = GridSolver::new(grid_from_rows(&rows), dict.clone());
+        solver.set_directions(&[EntryDir::Across]);
+        assert!(solver.solve());
+        assert!(solver.grid.is_filled());
+        for index in solver.grid.entry_indices() {
+            let word = solver.entry_word(index).unwrap();
+            assert_eq!(dict.contains(&word), index.dir == EntryDir::Across);
+        }
+    }
+
+    #[test]
+    fn fill_success_rate_counts_the_filled_grids() {
+        let dict = dict_from(&["CAT", "DOG"]);
+        let easy = grid_from_rows(&["...", "###", "..."]);
+        let impossible = grid_from_rows(&["...", "#.#", "#.#"]);
+        let grids = [easy.clone(), impossible.clone(), easy.clone(), impossible];
+        assert_eq!(fill_success_rate(&grids, &dict, 3, 1), 0.5);
+        assert_eq!(fill_success_rate(&grids[..1], &dict, 1, 1), 1.0);
+        // no attempts fill nothing
+        assert_eq!(fill_success_rate(&grids, &dict, 0, 1), 0.0);
+        assert_eq!(fill_success_rate(&[], &dict, 3, 1), 0.0);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn solve_many_matches_solving_one_by_one() {
+        let dict = dict_from(&[
+            "ARE", "CAT", "RUB", "BOX", "COD", "DOG", "FIG", "HUT", "JOT", "KIT",
+            "LOG", "MUD", "NIB", "POT", "SIP", "TOP", "VET", "WIG", "YET", "ZIP",
+        ]);
+        let rows: &[&[&str]] = &[&["...", ".#.", "..."], &["...", "###", "..."], &["Q..", "...", "..."], &["C..", "#.#", "..."]];
+        let grids = rows.iter().cycle().take(12).map(|rows| grid_from_rows(rows)).collect::<Vec<_>>();
+        let sequential = grids.iter()
+            .enumerate()
+            .map(|(i, grid)| {
+                let mut solver = GridSolver::with_seed(grid.clone(), dict.clone(), i as u64);
+                if solver.solve() { Some(solver.grid().to_string()) } else { None }
+            })
+            .collect::<Vec<_>>();
+        let parallel = solve_many(grids, &dict).into_iter()
+            .map(|grid| grid.map(|grid| grid.to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(parallel, sequential);
+        assert!(parallel.iter().any(|grid| grid.is_some()));
+        assert!(parallel.iter().any(|grid| grid.is_none()));
+    }
+
+    #[test]
+    fn solve_n_finds_distinct_fills() {
+        let grid = grid_from_rows(&["...", "###", "..."]);
+        let mut solver = GridSolver::new(grid, dict_from(&["CAT", "DOG"]));
+
+        let solutions = solver.solve_n(3);
+        assert_eq!(solutions.len(), 3);
+        let mut printed = solutions.iter().map(|grid| grid.to_string()).collect::<Vec<_>>();
+        printed.sort();
+        printed.dedup();
+        assert_eq!(printed.len(), 3);
+        let dict = dict_from(&["CAT", "DOG"]);
+        assert!(solutions.iter().all(|grid| is_valid_fill(grid, &dict)));
+
+        // nothing leaks out of the search
+        assert!(solver.added_words().is_empty());
+        assert!(solver.changes.is_empty());
+        assert_eq!(solver.unfilled_entries.len(), 2);
+        assert!(!solver.grid().is_filled());
+
+        // there are only four fills to find
+        assert_eq!(solver.solve_n(10).len(), 4);
+        assert!(solver.solve());
+    }
+
+    #[test]
+    fn resolve_region_alternatives_keeps_the_rest_of_the_grid() {
+        let rows = ["CAT#COT", "#R###A#", "#E###K#"];
+        let dict = dict_from(&["CAT", "ARE", "COT", "OAK", "BAT", "HAT", "ANT", "ASK"]);
+        let mut solver = GridSolver::new(grid_from_rows(&rows), dict.clone());
+        // the branch factor would only let one word through, but the region ignores it
+        solver.set_branch_factor(Some(1));
+        let original = solver.grid().clone();
+
+        // the left corner
+        let corner = (0..3)
+            .flat_map(|row| (0..3).map(move |col| GridCoord::new(row, col)))
+            .collect::<Vec<_>>();
+        let alternatives = solver.resolve_region_alternatives(&corner, 4);
+        assert_eq!(alternatives.len(), 4);
+        let mut printed = alternatives.iter().map(|grid| grid.to_string()).collect::<Vec<_>>();
+        printed.sort();
+        printed.dedup();
+        assert_eq!(printed.len(), 4);
+        for grid in &alternatives {
+            assert!(is_valid_fill(grid, &dict));
+            assert_ne!(grid.to_string(), original.to_string());
+            for row in 0..3 {
+                for col in 3..7 {
+                    let coord = GridCoord::new(row, col);
+                    assert_eq!(grid.get_cell(coord), original.get_cell(coord));
+                }
+            }
+        }
+
+        // the solver is left with the grid it started with
+        assert_eq!(solver.grid().to_string(), original.to_string());
+        assert!(solver.unfilled_entries.is_empty());
+        assert_eq!(solver.added_words().len(), 4);
+        assert_eq!(solver.branch_factor, Some(1));
+    }
+
+    #[test]
+    fn checkpoint_fires_every_so_many_nodes() {
+        // every word fits either entry, but none crosses another,
+        // so the search tries all five words and fails
+        let grid = grid_from_rows(&["...", "#.#", "#.#"]);
+        let dict = dict_from(&["CAT", "BAT", "HAT", "MAT", "RAT"]);
+        let mut solver = GridSolver::new(grid, dict);
+        let seen = Rc::new(RefCell::new(vec![]));
+        let sink = seen.clone();
+        solver.set_checkpoint(2, Box::new(move |stats: &SolveStats| sink.borrow_mut().push(stats.nodes)));
+
+        assert!(!solver.solve());
+        assert_eq!(solver.stats().nodes, 5);
+        assert_eq!(solver.stats().backtracks, 5);
+        assert_eq!(solver.stats().max_depth, 1);
+        assert_eq!(*seen.borrow(), vec![2, 4]);
+    }
+
+    #[test]
+    fn solve_stats_count_backtracks() {
+        let mut solver = GridSolver::new(grid_from_rows(&["..."]), dict_from(&["CAT"]));
+        let (solved, stats) = solver.solve_with_stats();
+        assert!(solved);
+        assert_eq!(stats.nodes, 1);
+        assert_eq!(stats.backtracks, 0);
+        assert_eq!(stats.max_depth, 1);
+        assert_eq!(stats, *solver.stats());
+
+        // the best word for the across entry leaves nothing for the down one
+        let grid = grid_from_rows(&["...", "#.#", "#.#"]);
+        let mut solver = GridSolver::new(grid, ranked_dict_from(&[("CAT", 90), ("OAK", 80), ("COT", 50)]));
+        assert!(solver.solve_ranked());
+        assert!(solver.stats().backtracks > 0);
+        assert!(solver.stats().elapsed > Duration::from_secs(0));
+    }
+
+    #[test]
+    fn expected_cost_finds_the_dead_end_sooner() {
+        // the three letter entry has the fewest words, but every one of them leaves
+        // the down entry alone, while no five letter word can follow any four letter one
+        let grid = grid_from_rows(&["...###", "#.####", "#.####", "#....."]);
+        let dict = dict_from(&[
+            "BAT", "CAT",
+            "ABLE", "ACHE", "AIDE", "ALSO", "AREA", "AUNT",
+            "BREAD", "CRANE", "DRINK",
+        ]);
+        let solve = |order| {
+            let mut solver = GridSolver::new(grid.clone(), dict.clone());
+            solver.set_branch_factor(None);
+            solver.set_variable_order(order);
+            let first = solver.next_entry();
+            let (solved, stats) = solver.solve_with_stats();
+            assert!(!solved);
+            (first, stats.nodes)
+        };
+        let (mrv_first, mrv_nodes) = solve(VariableOrder::MostConstrained);
+        let (cost_first, cost_nodes) = solve(VariableOrder::ExpectedCost);
+        assert_eq!(mrv_first, EntryIndex::default());
+        assert_eq!(cost_first, EntryIndex::try_from((3, EntryDir::Across)).unwrap());
+        // two words across the top, each followed by the three five letter words,
+        // against just the three five letter words
+        assert_eq!((mrv_nodes, cost_nodes), (8, 3));
+    }
+
+    #[test]
+    fn best_effort_keeps_the_deepest_partial_fill() {
+        // no five letter word can follow a four letter one
+        let grid = grid_from_rows(&["...###", "#.####", "#.####", "#....."]);
+        let dict = dict_from(&["BAT", "ABLE", "BREAD"]);
+        let mut solver = GridSolver::new(grid.clone(), dict.clone());
+        let (solved, partial) = solver.solve_best_effort();
+        assert!(!solved);
+        assert!(partial.entry_indices().into_iter().any(|index| partial.is_entry_filled(index)));
+        assert!(!partial.is_filled());
+        assert_eq!(solver.grid().cells, grid.cells);
+
+        let mut solver = GridSolver::new(grid_from_rows(&["..."]), dict);
+        let (solved, fill) = solver.solve_best_effort();
+        assert!(solved && fill.is_filled());
+    }
+
+    #[test]
+    fn solve_stats_record_the_peak_possible_fills() {
+        let dict = dict_from(&["CAT", "ARE", "TEA", "AXE", "RAT", "EAR"]);
+        let mut solver = GridSolver::new(grid_from_rows(&["C..", "...", "..."]), dict);
+        let start = solver.possible_fills.values().map(|fills| fills.len()).sum::<usize>();
+        let (solved, stats) = solver.solve_with_stats();
+        assert!(solved);
+        // every entry the search picks copies the words it's going to try,
+        // so more is held partway through than at the start
+        assert!(stats.peak_possible_fills_total > start);
+
+        // a single entry holds its six words, and with a branch factor of five tries five of them
+        let dict = dict_from(&["CAT", "ARE", "TEA", "AXE", "RAT", "EAR"]);
+        let mut solver = GridSolver::new(grid_from_rows(&["..."]), dict);
+        solver.set_branch_factor(Some(5));
+        let (solved, stats) = solver.solve_with_stats();
+        assert!(solved);
+        assert_eq!(stats.peak_possible_fills_total, 11);
+    }
+
+    #[test]
+    fn forward_checking_backtracks_less() {
+        let dict = dict_from(&[
+            "ARE", "CAT", "RUB", "BOX", "COD", "DOG", "FIG", "HUT", "JOT", "KIT",
+            "LOG", "MUD", "NIB", "POT", "SIP", "TOP", "VET", "WIG", "YET", "ZIP",
+        ]);
+        let grid = grid_from_rows(&["...", ".#.", "..."]);
+        let solve = |seed, checking| {
+            let mut solver = GridSolver::with_seed(grid.clone(), dict.clone(), seed);
+            solver.set_forward_checking(checking);
+            let (solved, stats) = solver.solve_with_stats();
+            ((solved, solver.grid().to_string()), stats.backtracks)
+        };
+        let (mut with, mut without) = (0, 0);
+        for seed in 0..10 {
+            let (checked_fill, checked_backtracks) = solve(seed, true);
+            let (fill, backtracks) = solve(seed, false);
+            // the same fills, for less work
+            assert_eq!(checked_fill, fill);
+            assert!(checked_backtracks <= backtracks);
+            with += checked_backtracks;
+            without += backtracks;
+        }
+        assert!(with < without);
+
+        // the best word for the across entry leaves nothing for the down one,
+        // so it's skipped without being filled
+        let grid = grid_from_rows(&["...", "#.#", "#.#"]);
+        let mut solver = GridSolver::new(grid, ranked_dict_from(&[("CAT", 90), ("OAK", 80), ("COT", 50)]));
+        solver.set_forward_checking(true);
+        assert!(solver.solve_ranked());
+        assert_eq!(solver.stats().backtracks, 0);
+        assert_eq!(solver.stats().nodes, 2);
+    }
+
+    #[test]
+    fn solve_with_limit_stops_when_the_budget_runs_out() {
+        // the search fails after five fills and five undos
+        let grid = grid_from_rows(&["...", "#.#", "#.#"]);
+        let dict = dict_from(&["CAT", "BAT", "HAT", "MAT", "RAT"]);
+        let mut solver = GridSolver::new(grid, dict);
+        assert_eq!(solver.solve_with_limit(100), SolveOutcome::Unsolvable);
+        assert_eq!(solver.solve_with_limit(3), SolveOutcome::BudgetExhausted);
+        assert!(solver.changes.is_empty());
+        assert!(solver.added_words().is_empty());
+        assert!(!solver.grid.is_entry_filled(EntryIndex::default()));
+
+        let grid = grid_from_rows(&["...", "###", "..."]);
+        let mut solver = GridSolver::new(grid, dict_from(&["CAT", "DOG"]));
+        assert_eq!(solver.solve_with_limit(2), SolveOutcome::Solved);
+        // the budget only applies to solve_with_limit
+        assert!(solver.solve());
+    }
+
+    #[test]
+    fn check_prefill_flags_impossible_letters() {
+        let grid = grid_from_rows(&["Q..", "...", "D.G"]);
+        let dict = dict_from(&["CAT", "DOG", "ARE", "TEA"]);
+        let solver = GridSolver::new(grid, dict);
+        // no word starts with Q, so the top row and first column have no fills
+        // DOG fits the bottom row and last column, and the rest are empty
+        let down = EntryIndex::try_from((1, EntryDir::Down)).unwrap();
+        assert_eq!(solver.check_prefill(), vec![EntryIndex::default(), down]);
+
+        let grid = grid_from_rows(&["C..", "...", "..."]);
+        assert!(GridSolver::new(grid, dict_from(&["CAT"])).check_prefill().is_empty());
+    }
+
+    #[test]
+    fn branch_factor_limits_the_words_tried() {
+        // only COT and OAK cross, so the across entry has to try COT
+        // out of twelve words, which five shuffled words often miss
+        let grid = grid_from_rows(&["...", "#.#", "#.#"]);
+        let dict = dict_from(&[
+            "BAT", "CAT", "EAT", "FAT", "HAT", "MAT", "PAT", "RAT", "SAT", "VAT", "COT", "OAK",
+        ]);
+        let solve = |seed, factor| {
+            let mut solver = GridSolver::new(grid.clone(), dict.clone());
+            solver.reseed(seed);
+            solver.set_branch_factor(factor);
+            solver.solve()
+        };
+        assert!((0..20).any(|seed| !solve(seed, Some(5))));
+        assert!((0..20).all(|seed| solve(seed, None)));
+    }
+
+    #[test]
+    fn solve_until_stops_at_the_deadline() {
+        let dict = dict_from(&["CAT", "ARE", "TEA", "AXE", "RAT", "EAR"]);
+        let mut solver = GridSolver::new(grid_from_rows(&["C..", "...", "..."]), dict.clone());
+        assert_eq!(solver.solve_until(&Deadline::after(Duration::from_secs(0))), None);
+        assert!(solver.added_words().is_empty());
+
+        // one deadline does for a whole batch
+        let deadline = Deadline::after(Duration::from_secs(60));
+        assert_eq!(solver.solve_until(&deadline), Some(true));
+        let mut other = GridSolver::new(grid_from_rows(&["Q..", "...", "..."]), dict);
+        assert_eq!(other.solve_until(&deadline), Some(false));
+    }
+
+    #[test]
+    fn portfolio_solves_what_one_config_cant() {
+        // the same grid as above: trying a single word for the across entry
+        // only works if it happens to be COT
+        let grid = grid_from_rows(&["...", "#.#", "#.#"]);
+        let dict = dict_from(&[
+            "BAT", "CAT", "EAT", "FAT", "HAT", "MAT", "PAT", "RAT", "SAT", "VAT", "COT", "OAK",
+        ]);
+        let config = |seed, branch_factor| SolverConfig {
+            seed,
+            variable_order: VariableOrder::MostConstrained,
+            branch_factor,
+        };
+        let solve = |configs: &[SolverConfig], budget| {
+            let mut solver = GridSolver::new(grid.clone(), dict.clone());
+            solver.solve_portfolio(configs, budget)
+        };
+        let stuck = (0..).find(|&seed| !solve(&[config(seed, Some(1))], 1000)).unwrap();
+
+        // the fixed config fails with the whole budget, the portfolio succeeds splitting it
+        assert!(!solve(&[config(stuck, Some(1))], 200));
+        assert!(solve(&[config(stuck, Some(1)), config(stuck, None)], 100));
+        assert!(!solve(&[], 100));
+
+        // the solver's own settings survive the portfolio
+        let mut solver = GridSolver::new(grid.clone(), dict.clone());
+        solver.set_branch_factor(Some(3));
+        solver.solve_portfolio(&[config(stuck, None)], 100);
+        assert_eq!(solver.branch_factor, Some(3));
+        assert_eq!(solver.variable_order, VariableOrder::MostConstrained);
+    }
+
+    #[test]
+    fn failure_report_finds_the_dead_end() {
+        // the across entry starts with Q, but no word in the dictionary does
+        let grid = grid_from_rows(&["#.#", "Q..", "#.#"]);
+        let mut solver = GridSolver::new(grid, dict_from(&["CAT", "DOG"]));
+        assert!(!solver.solve());
+        assert_eq!(solver.failure_report(), Some(EntryIndex::try_from((2, EntryDir::Across)).unwrap()));
+
+        // filling the across entry leaves the down entry without a fill
+        let grid = grid_from_rows(&["...", "#.#", "#.#"]);
+        let mut solver = GridSolver::new(grid, dict_from(&["CAT", "DOG"]));
+        assert!(!solver.solve_iterative());
+        assert_eq!(solver.failure_report(), Some(EntryIndex::try_from((2, EntryDir::Down)).unwrap()));
+
+        let grid = grid_from_rows(&["...", "###", "..."]);
+        let mut solver = GridSolver::new(grid, dict_from(&["CAT", "DOG"]));
+        assert!(solver.solve());
+        assert_eq!(solver.failure_report(), None);
+    }
+
+    #[test]
+    fn explain_rejection_gives_each_reason() {
+        // 1 across is C.., 1 down starts with C, 2 down and 3 across are blank
+        let mut solver = GridSolver::new(grid_from_rows(&["C..", ".#.", "..."]),
+                                         dict_from(&["CAT", "COT", "CUT", "TAT", "TOT", "ACT", "OAT"]));
+        let one_across = EntryIndex::try_from((1, EntryDir::Across)).unwrap();
+        let one_down = EntryIndex::try_from((1, EntryDir::Down)).unwrap();
+        let two_down = EntryIndex::try_from((2, EntryDir::Down)).unwrap();
+        let three_across = EntryIndex::try_from((3, EntryDir::Across)).unwrap();
+
+        assert_eq!(solver.explain_rejection(one_across, &Word::from("CATS")), Some(Rejection::LengthMismatch));
+        assert_eq!(solver.explain_rejection(one_across, &Word::from("TAT")), Some(Rejection::PatternConflict(0)));
+        assert_eq!(solver.explain_rejection(one_across, &Word::from("CAB")), Some(Rejection::NotInDictionary));
+        assert_eq!(solver.explain_rejection(one_across, &Word::from("CUT")), None);
+        // 1 down would have to be C.O, which isn't a word
+        assert_eq!(solver.explain_rejection(three_across, &Word::from("OAT")), Some(Rejection::CrossingDeadEnd(one_down)));
+        assert_eq!(solver.explain_rejection(one_across, &Word::from("CAT")), None);
+
+        solver.add_blocked(&Word::from("COT"));
+        assert_eq!(solver.explain_rejection(one_across, &Word::from("COT")), Some(Rejection::Blacklisted));
+
+        solver.replace_word(one_across, &Word::from("CAT")).unwrap();
+        assert_eq!(solver.explain_rejection(one_across, &Word::from("CAT")), None);
+        assert_eq!(solver.explain_rejection(two_down, &Word::from("CAT")), Some(Rejection::PatternConflict(0)));
+        assert_eq!(solver.explain_rejection(one_down, &Word::from("CAT")), Some(Rejection::AlreadyUsed));
+    }
+
+    #[test]
+    fn fill_entropy_rewards_varied_letters() {
+        let entropy = |rows: &[&str]| GridSolver::new(grid_from_rows(rows), dict_from(&[])).fill_entropy();
+        assert_eq!(entropy(&["..."]), 0.0);
+        assert_eq!(entropy(&["AAA"]), 0.0);
+        assert_eq!(entropy(&["AAA", "###", "BBB"]), 1.0);
+        assert!((entropy(&["CAT", "###", "DOG"]) - 6f64.log2()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn current_entries_show_partial_letters() {
+        let mut solver = GridSolver::new(grid_from_rows(&["...", "#.#", "..."]), dict_from(&["CAT", "ARE", "EGG"]));
+        let id = solver.dict().word_id(&Word::from("CAT")).unwrap();
+        solver.fill(EntryIndex::default(), id);
+        let entries = solver.current_entries()
+            .into_iter()
+            .map(|(index, entry)| (index, Pattern::new(&entry.letters).to_string()))
+            .collect::<Vec<_>>();
+        let across = |number| EntryIndex::try_from((number, EntryDir::Across)).unwrap();
+        let down = EntryIndex::try_from((2, EntryDir::Down)).unwrap();
+        assert_eq!(entries, vec![
+            (across(1), "CAT".to_string()),
+            (down, "A..".to_string()),
+            (across(3), "...".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn difficulty_heatmap_counts_every_unfilled_entry() {
+        let grid = grid_from_rows(&["C..", "...", "TEA"]);
+        let solver = GridSolver::new(grid, dict_from(&["CAT", "COT", "CUT", "ARE", "TEA", "AXE", "ORE"]));
+        let heatmap = solver.difficulty_heatmap();
+        // TEA along the bottom is already filled
+        assert_eq!(heatmap.len(), 5);
+        assert_eq!(heatmap.len(), solver.unfilled_entries.len());
+        let across = |number| EntryIndex::try_from((number, EntryDir::Across)).unwrap();
+        let down = |number| EntryIndex::try_from((number, EntryDir::Down)).unwrap();
+        assert_eq!(heatmap[&across(1)], 3);
+        assert_eq!(heatmap[&across(4)], 7);
+        assert_eq!(heatmap[&down(1)], 3);
+        assert_eq!(heatmap[&down(2)], 3);
+        assert_eq!(heatmap[&down(3)], 1);
+    }
+
+    #[test]
+    fn impossible_letters_complement_the_valid_ones() {
+        // the top left cell starts both ..T across and a blank down entry
+        let solver = GridSolver::new(grid_from_rows(&["..T", ".#.", "..."]),
+                                     dict_from(&["CAT", "COT", "BAT", "OAT", "ACT", "BOA", "TOE"]));
+        let letters = |word: &str| Word::from(word).letters.into_iter().collect::<HashSet<_>>();
+        let coord = GridCoord::new(0, 0);
+        // ..T across allows A, B, C and O, the blank down entry allows T too
+        assert_eq!(solver.valid_letters_at(coord), letters("ABCO"));
+        let impossible = solver.impossible_letters_at(coord);
+        assert_eq!(impossible.len(), 22);
+        assert!(impossible.is_disjoint(&solver.valid_letters_at(coord)));
+        let all = impossible.union(&solver.valid_letters_at(coord)).cloned().collect::<HashSet<_>>();
+        assert_eq!(all, letters("ABCDEFGHIJKLMNOPQRSTUVWXYZ"));
+
+        // a filled cell only allows its own letter, a black cell allows nothing
+        assert_eq!(solver.valid_letters_at(GridCoord::new(0, 2)), letters("T"));
+        assert_eq!(solver.impossible_letters_at(GridCoord::new(1, 1)).len(), 26);
+    }
+
+    #[test]
+    fn minimal_conflict_set_covers_the_unsatisfiable_region() {
+        // no word's middle letter starts another word, so the left region can't be filled
+        // the right region only needs two words starting with the same letter
+        let grid = grid_from_rows(&["...#...", "#.##.##", "#.##.##"]);
+        let dict = dict_from(&["CAT", "DOG", "COT"]);
+        let solver = GridSolver::new(grid, dict);
+        let across = EntryIndex::default();
+        let down = EntryIndex::try_from((2, EntryDir::Down)).unwrap();
+        assert_eq!(solver.minimal_conflict_set(), vec![across, down]);
+
+        let grid = grid_from_rows(&["...#...", "#.##.##", "#.##.##"]);
+        let dict = dict_from(&["CAT", "ARE", "COT"]);
+        let solver = GridSolver::new(grid, dict);
+        assert!(solver.minimal_conflict_set().is_empty());
+    }
+
+    #[test]
+    fn min_quality_rejects_a_mediocre_fill() {
+        // CAT is the best word, but only the weak CODE fits down from it
+        let grid = grid_from_rows(&["...", ".##", ".##", ".##"]);
+        let dict = ranked_dict_from(&[("CAT", 90), ("CODE", 41), ("BAT", 70), ("BUGS", 70)]);
+
+        let mut greedy = GridSolver::new(grid.clone(), dict.clone());
+        assert!(greedy.solve_ranked());
+        assert_eq!(greedy.average_score(), 65.5);
+
+        let mut picky = GridSolver::new(grid.clone(), dict.clone());
+        assert!(picky.solve_ranked_min_quality(68.0));
+        assert_eq!(picky.average_score(), 70.0);
+
+        // no fill is that good, so the grid is left empty
+        let mut impossible = GridSolver::new(grid, dict);
+        assert!(!impossible.solve_ranked_min_quality(80.0));
+        assert_eq!(impossible.grid().cells, grid_from_rows(&["...", ".##", ".##", ".##"]).cells);
+    }
+
+    #[test]
+    fn improving_fills_keep_getting_better() {
+        // the same grid as above: CAT leads to a fill averaging 65.5, then BAT to one averaging 70
+        let rows = ["...", ".##", ".##", ".##"];
+        let dict = ranked_dict_from(&[("CAT", 90), ("CODE", 41), ("BAT", 70), ("BUGS", 70), ("BOGS", 60)]);
+        let mut solver = GridSolver::new(grid_from_rows(&rows), dict);
+        let fills = solver.improving_fills().collect::<Vec<_>>();
+        let scores = fills.iter().map(|&(_, score)| score).collect::<Vec<_>>();
+        assert_eq!(scores, vec![65.5, 70.0]);
+        assert!(fills.iter().all(|(grid, _)| grid.is_filled()));
+        assert_eq!(solver.grid().cells, grid_from_rows(&rows).cells);
+
+        // stopping early still puts the grid back
+        assert_eq!(solver.improving_fills().next().unwrap().1, 65.5);
+        assert_eq!(solver.grid().cells, grid_from_rows(&rows).cells);
+    }
+
+    #[test]
+    fn undoing_a_repeated_word_keeps_the_prefilled_copy() {
+        // CAT is prefilled across the top, then put in the bottom by hand as well
+        let mut solver = GridSolver::new(grid_from_rows(&["CAT", "#.#", "..."]), dict_from(&["CAT", "ACE"]));
+        assert_eq!(solver.added_words().get(&Word::from("CAT")), Some(&1));
+        let bottom = EntryIndex::try_from((3, EntryDir::Across)).unwrap();
+        let id = solver.dict().word_id(&Word::from("CAT")).unwrap();
+        solver.fill(bottom, id);
+        assert_eq!(solver.added_words().get(&Word::from("CAT")), Some(&2));
+        solver.undo_last_fill();
+        assert_eq!(solver.added_words().get(&Word::from("CAT")), Some(&1));
+        assert_eq!(solver.explain_rejection(bottom, &Word::from("CAT")), Some(Rejection::AlreadyUsed));
+        assert_eq!(solver.explain_rejection(EntryIndex::default(), &Word::from("CAT")), None);
+
+        // the down entry can only be ACE, which leaves the bottom nothing but
+        // CAT or ACE again, so every fill a solve makes is undone
+        // and the prefilled copy is still counted once
+        assert!(!solver.solve());
+        assert_eq!(solver.added_words().get(&Word::from("CAT")), Some(&1));
+        assert!(solver.solve_n(2).is_empty());
+        assert_eq!(solver.added_words().get(&Word::from("CAT")), Some(&1));
+        assert_eq!(solver.added_words().len(), 1);
+    }
+
+    #[test]
+    fn ranked_solving_tries_the_best_word_first() {
+        // the across entry has the fewest words so it's filled first
+        // DOG scores best but no four letter word starts with D,
+        // so CAT is the best word it can have, and CODE the best word crossing it
+        let grid = grid_from_rows(&["...", ".##", ".##", ".##"]);
+        let dict = ranked_dict_from(&[
+            ("DOG", 95), ("CAT", 90), ("BAT", 70),
+            ("BUGS", 60), ("BEAD", 55), ("CODE", 50), ("CUBE", 45),
+        ]);
+        let mut solver = GridSolver::new(grid, dict);
+        assert!(solver.solve_ranked());
+        assert!(solver.added_words().contains_key(&Word::from("CAT")));
+        assert_eq!(solver.total_score(), 140);
+    }
+
+    #[test]
+    fn ranked_solving_tries_every_word_by_default() {
+        // the across entry has the fewest words so it's filled first,
+        // but ZOOS is the only four letter word starting with one of them,
+        // so only ZAP can go across, and it's seventh by score
+        let grid = grid_from_rows(&["...", ".##", ".##", ".##"]);
+        let dict = ranked_dict_from(&[
+            ("CAT", 90), ("BAT", 85), ("HAT", 80), ("MAT", 75), ("RAT", 70), ("SAT", 65), ("ZAP", 60),
+            ("QUAY", 50), ("QUIZ", 50), ("QUIT", 50), ("YOGA", 50), ("YAWN", 50), ("YELP", 50),
+            ("YOKE", 50), ("YURT", 50), ("ZOOS", 50),
+        ]);
+        let mut solver = GridSolver::new(grid.clone(), dict.clone());
+        assert!(solver.solve_ranked());
+        assert!(solver.added_words().contains_key(&Word::from("ZAP")));
+
+        // but a branch factor still applies when one is set
+        let mut solver = GridSolver::new(grid, dict);
+        solver.set_branch_factor(Some(5));
+        assert!(!solver.solve_ranked());
+    }
+
+    #[test]
+    fn required_words_are_in_every_fill() {
+        let fox = Word::from("FOX");
+        let dict = dict_from(&["CAT", "DOG", "EMU", "FOX", "GNU"]);
+        let mut solver = GridSolver::new(grid_from_rows(&["...", "###", "..."]), dict.clone());
+        assert!(solver.require_word(&fox));
+        let fills = solver.solve_n(20);
+        assert!(fills.len() > 1);
+        for fill in &fills {
+            assert!(fill.entries().iter().any(|entry| Pattern::new(&entry.letters) == Pattern::from("FOX")));
+        }
+        for seed in 0..5 {
+            solver.reseed(seed);
+            assert!(solver.solve_iterative());
+            assert!(solver.added_words().contains_key(&fox));
+            solver.reset();
+        }
+
+        // nothing four letters long fits
+        assert!(!solver.require_word(&Word::from("LYNX")));
+
+        // a word in a set place stays there
+        let bottom = EntryIndex::try_from((2, EntryDir::Across)).unwrap();
+        let mut solver = GridSolver::new(grid_from_rows(&["...", "###", "C.."]), dict);
+        assert_eq!(solver.require_at(bottom, &fox), Err(Rejection::PatternConflict(0)));
+        solver.require_at(EntryIndex::default(), &fox).unwrap();
+        assert!(solver.solve());
+        solver.undo_last_fill();
+        let top = solver.grid().get_entry(EntryIndex::default()).unwrap();
+        assert_eq!(Pattern::new(&top.letters), Pattern::from("FOX"));
+    }
+
+    #[test]
+    fn builder_settings_take_effect() {
+        let dict = dict_from(&["CAT", "COT", "CUT", "DOG", "EMU"]);
+        let grid = grid_from_rows(&["...", "###", "..."]);
+        let solver = GridSolverBuilder::new()
+            .seed(3)
+            .branch_factor(2)
+            .score_range(Some(10), Some(20))
+            .variable_order(VariableOrder::ExpectedCost)
+            .forward_checking(true)
+            .block(Word::from("CAT"))
+            .require(Word::from("EMU"))
+            .build(grid.clone(), dict.clone());
+        assert_eq!(solver.branch_factor, Some(2));
+        assert_eq!((solver.score_lower, solver.score_upper), (Some(10), Some(20)));
+        assert_eq!(solver.variable_order, VariableOrder::ExpectedCost);
+        assert!(solver.forward_checking);
+        assert!(solver.blocklist.contains(&solver.dict().word_id(&Word::from("CAT")).unwrap()));
+        assert_eq!(solver.required_words, vec![Word::from("EMU")]);
+
+        // the seed gives the same fills as with_seed
+        let mut built = solver;
+        let mut seeded = GridSolver::with_seed(grid, dict, 3);
+        seeded.set_branch_factor(Some(2));
+        seeded.set_variable_order(VariableOrder::ExpectedCost);
+        seeded.set_forward_checking(true);
+        seeded.add_blocked(&Word::from("CAT"));
+        seeded.require_word(&Word::from("EMU"));
+        assert!(built.solve() && seeded.solve());
+        assert_eq!(built.grid().to_string(), seeded.grid().to_string());
+        assert!(built.added_words().contains_key(&Word::from("EMU")));
+        assert!(!built.added_words().contains_key(&Word::from("CAT")));
+    }
+
+    #[test]
+    fn blocked_words_never_appear_in_a_fill() {
+        let cat = Word::from("CAT");
+        for seed in 0..10 {
+            let mut solver = GridSolver::with_seed(grid_from_rows(&["..."]), dict_from(&["CAT", "DOG"]), seed);
+            solver.add_blocked(&cat);
+            assert!(solver.solve());
+            assert!(!solver.added_words().contains_key(&cat));
+        }
+
+        // CAT would be the ranked solver's first choice
+        let mut solver = GridSolver::new(grid_from_rows(&["..."]), ranked_dict_from(&[("CAT", 90), ("DOG", 50)]));
+        solver.add_blocked(&cat);
+        assert!(solver.solve_ranked());
+        assert!(!solver.added_words().contains_key(&cat));
+
+        // and a grid only CAT fits can't be filled
+        let mut solver = GridSolver::new(grid_from_rows(&["C.."]), dict_from(&["CAT", "DOG"]));
+        solver.add_blocked(&cat);
+        assert!(!solver.solve());
+    }
+
+    #[test]
+    fn soft_constraints_reorder_without_forbidding() {
+        fn first_candidate<T: DictLookup>(solver: &mut GridSolver<T>) -> Word {
+            let id = solver.candidates(EntryIndex::default())[0];
+            solver.dict().word(id).clone()
+        }
+        let penalize_cat = |penalty| -> SoftConstraintFn {
+            Box::new(move |word: &Word, _| if *word == Word::from("CAT") { penalty } else { 0 })
+        };
+
+        // CAT has to be tried last however the words are shuffled
+        for seed in 0..10 {
+            let mut solver = GridSolver::with_seed(grid_from_rows(&["..."]), dict_from(&["CAT", "COT"]), seed);
+            solver.add_soft_constraint(penalize_cat(1));
+            assert_eq!(first_candidate(&mut solver), Word::from("COT"));
+        }
+
+        // the penalties add up and come off the ranked score
+        let mut solver = GridSolver::new(grid_from_rows(&["..."]), ranked_dict_from(&[("CAT", 90), ("COT", 80)]));
+        solver.use_ranked();
+        solver.add_soft_constraint(penalize_cat(6));
+        assert_eq!(first_candidate(&mut solver), Word::from("CAT"));
+        solver.add_soft_constraint(penalize_cat(6));
+        assert_eq!(first_candidate(&mut solver), Word::from("COT"));
+
+        // but a penalized word can still be used
+        let mut solver = GridSolver::new(grid_from_rows(&["..."]), dict_from(&["CAT"]));
+        solver.add_soft_constraint(penalize_cat(100));
+        assert!(solver.solve());
+        assert!(solver.added_words().contains_key(&Word::from("CAT")));
+    }
+
+    #[test]
+    fn score_range_limits_the_ranked_words() {
+        let rows = ["...", ".##", ".##", ".##"];
+        let dict = ranked_dict_from(&[("CAT", 90), ("CODE", 41), ("BAT", 70), ("BUGS", 70), ("BOGS", 20)]);
+
+        // CODE is under the floor, so CAT can't be used
+        let mut solver = GridSolver::new(grid_from_rows(&rows), dict.clone());
+        solver.set_score_range(Some(60), None);
+        assert!(solver.solve_ranked());
+        assert_eq!(solver.average_score(), 70.0);
+
+        // no word is that good
+        let mut solver = GridSolver::new(grid_from_rows(&rows), dict.clone());
+        solver.set_score_range(Some(100), None);
+        assert!(!solver.solve_ranked());
+
+        // with no floor but a ceiling of 50 no three letter word is left,
+        // and with a ceiling of 70 only BAT is
+        let mut solver = GridSolver::new(grid_from_rows(&rows), dict);
+        solver.set_score_range(None, Some(50));
+        assert!(!solver.solve_ranked());
+        solver.set_score_range(None, Some(70));
+        assert!(solver.solve_ranked());
+        assert_eq!(solver.average_score(), 70.0);
+    }
+
+    #[test]
+    fn vowel_balance_tries_balanced_words_first() {
+        let dict = dict_from(&["TSKS", "OOZE", "RATE", "AIEE"]);
+        for seed in 0..10 {
+            let mut solver = GridSolver::with_seed(grid_from_rows(&["...."]), dict.clone(), seed);
+            solver.set_vowel_balance(true);
+            assert!(solver.solve());
+            assert_eq!(solver.grid().to_string(), grid_from_rows(&["RATE"]).to_string());
+        }
+    }
+
+    #[test]
+    fn vowel_balance_keeps_preferred_words_first() {
+        let dict = ranked_dict_from(&[("TSKS", 50), ("RATE", 60)]);
+        let mut solver = GridSolver::new(grid_from_rows(&["...."]), dict);
+        solver.set_vowel_balance(true);
+        solver.prefer_words(&[Word::from("TSKS")], 20);
+        assert!(solver.solve_ranked());
+        assert!(solver.added_words().contains_key(&Word::from("TSKS")));
+
+        // but it still breaks a tie with an equally good word
+        let dict = ranked_dict_from(&[("TSKS", 50), ("RATE", 60), ("OOZE", 70)]);
+        let mut solver = GridSolver::new(grid_from_rows(&["...."]), dict);
+        solver.set_vowel_balance(true);
+        solver.prefer_words(&[Word::from("TSKS")], 20);
+        assert!(solver.solve_ranked());
+        assert!(solver.added_words().contains_key(&Word::from("OOZE")));
+    }
+
+    #[test]
+    fn natick_threshold_keeps_obscure_words_apart() {
+        let rows = ["...", "##.", "##.", "##."];
+        let dict = ranked_dict_from(&[("ZIP", 55), ("TOT", 50), ("PLEB", 52), ("TUBA", 90), ("XRAY", 45)]);
+
+        // ZIP beats TOT, and PLEB is the only down word it leaves
+        let mut solver = GridSolver::new(grid_from_rows(&rows), dict.clone());
+        assert!(solver.solve_ranked());
+        assert!(solver.added_words().contains_key(&Word::from("ZIP")));
+        assert!(solver.added_words().contains_key(&Word::from("PLEB")));
+
+        // both are obscure under 60, so TOT has to cross TUBA instead
+        let mut solver = GridSolver::new(grid_from_rows(&rows), dict);
+        solver.set_max_natick_obscurity(60);
+        assert!(solver.solve_ranked());
+        assert!(solver.added_words().contains_key(&Word::from("TOT")));
+        assert!(solver.added_words().contains_key(&Word::from("TUBA")));
+    }
+
+    #[test]
+    fn preferred_words_beat_higher_scores() {
+        let grid = grid_from_rows(&["..."]);
+        let dict = ranked_dict_from(&[("CAT", 90), ("DOG", 60), ("EMU", 20)]);
+        let across = EntryIndex::default();
+
+        let mut solver = GridSolver::new(grid.clone(), dict.clone());
+        assert!(solver.solve_ranked());
+        assert_eq!(solver.entry_word(across), Some(Word::from("CAT")));
+
+        let mut solver = GridSolver::new(grid.clone(), dict.clone());
+        solver.prefer_words(&[Word::from("DOG")], 50);
+        assert!(solver.solve_ranked());
+        assert_eq!(solver.entry_word(across), Some(Word::from("DOG")));
+
+        // EMU is normally too low scoring to be used at all
+        let mut solver = GridSolver::new(grid, dict);
+        solver.prefer_words(&[Word::from("EMU"), Word::from("YAK")], 100);
+        assert!(solver.solve_ranked());
+        assert_eq!(solver.entry_word(across), Some(Word::from("EMU")));
+    }
+
+    #[test]
+    fn worst_best_score_picks_the_weakest_entry() {
+        let grid = grid_from_rows(&["...#", "####", "...."]);
+        let short = EntryIndex::default();
+        let long = EntryIndex::try_from((2, EntryDir::Across)).unwrap();
+        // the short entry has the fewest fills, but the long one has the worse best fill
+        let dict = ranked_dict_from(&[("CAT", 90), ("LION", 45), ("BEAR", 50)]);
+        let mut solver = GridSolver::new(grid, dict);
+        assert_eq!(solver.next_entry(), short);
+
+        solver.set_variable_order(VariableOrder::WorstBestScore);
+        solver.use_ranked();
+        assert_eq!(solver.next_entry(), long);
+        assert!(solver.solve_ranked());
+        assert_eq!(solver.entry_word(short), Some(Word::from("CAT")));
+    }
+
+    #[test]
+    fn per_slot_cap_limits_attempts() {
+        // a cap of one still fills entries that don't constrain each other
+        let grid = grid_from_rows(&["...", "###", "...", "###", "..."]);
+        let dict = dict_from(&["CAT", "DOG", "EMU", "YAK", "GNU", "ELK"]);
+        let mut solver = GridSolver::new(grid, dict.clone());
+        solver.set_per_slot_cap(1);
+        assert!(solver.solve());
+        assert!(is_valid_fill(&solver.grid, &dict));
+
+        // only CAT and ARE cross, so whichever entry goes first has to find its one word
+        // the same seed tries words in the same order with or without the cap,
+        // so the capped search fills the grid exactly when the first word tried was right
+        let grid = grid_from_rows(&["...", "#.#", "#.#"]);
+        let dict = dict_from(&["CAT", "ARE", "DOG", "EMU", "GNU", "ELK"]);
+        let mut capped_failures = 0;
+        for seed in 0..10 {
+            let mut uncapped = GridSolver::with_seed(grid.clone(), dict.clone(), seed);
+            uncapped.set_branch_factor(None);
+            assert!(uncapped.solve());
+            let needed = *uncapped.slot_attempts.values().max().unwrap();
+
+            let mut capped = GridSolver::with_seed(grid.clone(), dict.clone(), seed);
+            capped.set_branch_factor(None);
+            capped.set_per_slot_cap(1);
+            let solved = capped.solve();
+            assert_eq!(solved, needed == 1);
+            assert!(capped.slot_attempts.values().all(|&attempts| attempts <= 1));
+            if solved {
+                assert_eq!(capped.grid().to_string(), uncapped.grid().to_string());
+            } else {
+                capped_failures += 1;
+            }
+        }
+        // some seeds try the right word first and some don't
+        assert!(capped_failures > 0 && capped_failures < 10);
+
+        // a cap of zero can't try anything
+        let grid = grid_from_rows(&["..."]);
+        let mut solver = GridSolver::new(grid, dict);
+        solver.set_per_slot_cap(0);
+        assert!(!solver.solve());
+        assert!(!solver.solve_iterative());
+    }
+
+    #[test]
+    fn local_restarts_escape_dead_ends() {
+        // only a couple of pairs of words cross each other, so most picks for
+        // the first entry leave the other one with nothing
+        let grid = grid_from_rows(&["...", "#.#", "#.#"]);
+        let dict = dict_from(&[
+            "ARE", "CAT", "RUB", "BOX", "COD", "DOG", "FIG", "HUT", "JOT", "KIT",
+            "LOG", "MUD", "NIB", "POT", "SIP", "TOP", "VET", "WIG", "YET", "ZIP",
+            "BUD", "CUP", "DIM", "FUN", "GUM", "HOP", "JIG", "LID", "MOB", "NUT",
+        ]);
+        // with the same seed, a branch factor of five and a budget of 20 steps,
+        // only the search with restarts finds a fill
+        let mut solver = GridSolver::with_seed(grid.clone(), dict.clone(), 7);
+        solver.set_branch_factor(Some(5));
+        solver.set_local_restarts(2, 100);
+        assert_eq!(solver.solve_with_limit(20), SolveOutcome::Solved);
+        assert!(is_valid_fill(&solver.grid, &dict));
+        assert!(solver.restarts_used > 0);
+        assert!(solver.stats().nodes + solver.stats().backtracks <= 20);
+
+        let mut plain = GridSolver::with_seed(grid, dict, 7);
+        plain.set_branch_factor(Some(5));
+        assert_ne!(plain.solve_with_limit(20), SolveOutcome::Solved);
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_fill() {
+        let dict = dict_from(&[
+            "ARE", "CAT", "RUB", "BOX", "COD", "DOG", "FIG", "HUT", "JOT", "KIT",
+            "LOG", "MUD", "NIB", "POT", "SIP", "TOP", "VET", "WIG", "YET", "ZIP",
+        ]);
+        let grid = grid_from_rows(&["...", ".#.", "..."]);
+        let fill = |seed| {
+            let mut solver = GridSolver::with_seed(grid.clone(), dict.clone(), seed);
+            let solved = solver.solve();
+            (solved, solver.grid().to_string())
+        };
+        let first = fill(42);
+        assert!(first.0);
+        assert_eq!(first, fill(42));
+        assert!((0..5).any(|seed| fill(seed) != first));
+    }
+
+    #[test]
+    fn interned_words_give_identical_solutions() {
+        // the words are interned in whatever order the dictionary hands them over,
+        // so the ids differ between the solvers but the fills shouldn't
+        let words = [
+            "ARE", "CAT", "RUB", "BOX", "COD", "DOG", "FIG", "HUT", "JOT", "KIT",
+            "LOG", "MUD", "NIB", "POT", "SIP", "TOP", "VET", "WIG", "YET", "ZIP",
+        ];
+        let mut reversed = words.to_vec();
+        reversed.reverse();
+        let rows = ["...", ".#.", "..."];
+        let fills = (0..2)
+            .map(|i| {
+                let dict = if i == 0 { dict_from(&words) } else { dict_from(&reversed) };
+                let mut solver = GridSolver::new(grid_from_rows(&rows), dict);
+                solver.reseed(3);
+                let solved = solver.solve();
+                (solved, solver.grid.to_string())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(fills[0], fills[1]);
+    }
+
+    #[test]
+    fn solve_iterative_fills_many_entries() {
+        // 100 independent three letter entries separated by black rows
+        let rows: Vec<&str> = (0..199)
+            .map(|i| if i % 2 == 0 { "..." } else { "###" })
+            .collect();
+        let grid = grid_from_rows(&rows);
+        assert_eq!(grid.entry_indices().len(), 100);
+
+        let dict = dict_from(&["CAT", "DOG", "EMU", "YAK", "GNU", "ELK", "APE", "RAM"]);
+        let mut solver = GridSolver::new(grid, dict.clone());
+        assert!(solver.solve_iterative());
+        assert!(is_valid_fill(&solver.grid, &dict));
+    }
+
+    #[test]
+    fn solve_iterative_reports_failure() {
+        // no word starts with the middle letter of another
+        let grid = grid_from_rows(&["...", "#.#", "#.#"]);
+        let dict = dict_from(&["CAT", "DOG"]);
+        let mut solver = GridSolver::new(grid, dict);
+        assert!(!solver.solve_iterative());
+        assert_eq!(solver.changes.len(), 0);
+        assert!(!solver.grid.is_entry_filled(EntryIndex::default()));
+    }
 }
@@ -4,6 +4,10 @@ extern crate try_from;
 extern crate log;
 extern crate env_logger;
 extern crate unidecode;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(test)]
+extern crate serde_json;
 
 pub mod basic_types;
 pub mod dict;
@@ -1,15 +1,22 @@
 extern crate clap;
 use clap::{Arg, App};
 
-#[macro_use]
 extern crate log;
 extern crate env_logger;
 
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+
 extern crate gridsolver;
+use gridsolver::basic_types::Word;
 use gridsolver::dict::{RankedDictionary, Dictionary, RankedDict, UnrankedDict};
 use gridsolver::grid::*;
 
 fn main() {
+    // warnings, e.g. about impossible prefilled letters, are shown with RUST_LOG=warn
+    env_logger::init().unwrap();
+
     // get the command line arguments
     let matches = App::new("GridSolver")
         .about("Fills in empty crossword grids with dictionary words")
@@ -30,10 +37,19 @@ fn main() {
              .takes_value(true)
              .default_value("./assets/grid1.txt")
              .help("The file to load the grid from"))
+        .arg(Arg::with_name("exclude")
+             .short("x")
+             .long("exclude")
+             .takes_value(true)
+             .help("A file of words, one per line, that may not appear in the fill"))
         .get_matches();
     let dict_path = matches.value_of("dict").unwrap();
     let grid_path = matches.value_of("grid").unwrap();
     let dict_ranked = matches.is_present("ranked");
+    let excluded = match matches.value_of("exclude") {
+        Some(path) => load_words(path).expect("could not load excluded words"),
+        None => vec![],
+    };
 
     // load the dictionary and grid
     let grid = Grid::from_file(grid_path).expect("could not load grid");
@@ -43,8 +59,12 @@ fn main() {
     if dict_ranked {
         let dict = <RankedDictionary as RankedDict>::from_file(dict_path).expect("could not load dict");
         let mut solver = GridSolver::new(grid, dict);
+        for word in &excluded {
+            solver.add_blocked(word);
+        }
         solver.solve_ranked();
         println!("{}", solver);
+        print_summary(solver.grid());
         // let mut added_words = solver.added_words.iter().cloned().collect::<Vec<_>>();
         // if added_words.is_empty() {
         //     println!("sorry!");
@@ -60,11 +80,33 @@ fn main() {
         //     print!("{}, ", word);
         // }
         // println!();
-        println!("{}", solver.average_score());
+        println!("average score: {:.1}", solver.average_score());
+        println!("total score: {}", solver.total_score());
     } else {
         let dict = <Dictionary as UnrankedDict>::from_file(dict_path).expect("could not load dict");
         let mut solver = GridSolver::new(grid, dict);
+        for word in &excluded {
+            solver.add_blocked(word);
+        }
         solver.solve();
         println!("{}", solver);
+        print_summary(solver.grid());
     };
 }
+
+// load a file of words, one per line
+fn load_words(path: &str) -> io::Result<Vec<Word>> {
+    let mut entire = String::new();
+    File::open(path)?.read_to_string(&mut entire)?;
+    Ok(entire.lines()
+        .map(Word::from)
+        .filter(|word| word.size() > 0)
+        .collect())
+}
+
+// print some statistics about the grid
+fn print_summary(grid: &Grid) {
+    println!("words: {}", grid.entry_indices().len());
+    println!("black cells: {:.1}%", 100.0 * grid.black_cell_ratio());
+    println!("average word length: {:.1}", grid.average_entry_len());
+}
@@ -0,0 +1,54 @@
+use std::env;
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::Command;
+
+// writes the contents to a file in the temp directory and returns its path
+fn temp_file(name: &str, contents: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("gridsolver_cli_{}_{}", std::process::id(), name));
+    File::create(&path).unwrap().write_all(contents.as_bytes()).unwrap();
+    path
+}
+
+// runs the binary with the given arguments and returns what it printed
+fn run(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_gridsolver"))
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn prints_a_summary_after_solving() {
+    let grid = temp_file("summary_grid.txt", "3, 3\n...\n.##\n.##\n");
+    let dict = temp_file("summary_dict.txt", "cat\ncow\n");
+    let output = run(&["-g", grid.to_str().unwrap(), "-d", dict.to_str().unwrap()]);
+    let summary = output.lines().skip_while(|line| !line.starts_with("words:")).collect::<Vec<_>>();
+    assert_eq!(summary, vec!["words: 2", "black cells: 44.4%", "average word length: 3.0"]);
+}
+
+#[test]
+fn prints_the_average_score_when_ranked() {
+    let grid = temp_file("ranked_grid.txt", "1, 3\n...\n");
+    let dict = temp_file("ranked_dict.txt", "cat;50\n");
+    let output = run(&["-g", grid.to_str().unwrap(), "-d", dict.to_str().unwrap(), "-r"]);
+    assert!(output.contains("CAT"));
+    assert!(output.contains("words: 1\n"));
+    assert!(output.contains("average score: 50.0\n"));
+}
+
+#[test]
+fn excluded_words_never_appear() {
+    let grid = temp_file("exclude_grid.txt", "1, 3\n...\n");
+    let dict = temp_file("exclude_dict.txt", "cat\ndog\n");
+    let exclude = temp_file("exclude_words.txt", "cat\n");
+    for _ in 0..5 {
+        let output = run(&["-g", grid.to_str().unwrap(), "-d", dict.to_str().unwrap(),
+                           "--exclude", exclude.to_str().unwrap()]);
+        assert!(output.contains("DOG"));
+        assert!(!output.contains("CAT"));
+    }
+}